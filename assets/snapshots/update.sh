@@ -0,0 +1,22 @@
+#!/bin/sh
+# Updates the snapshots of the default framework and model that are embedded in the binary.
+# Run it before building a release: `./assets/snapshots/update.sh [framework ref] [model ref]`
+set -e
+cd "$(dirname "$0")"
+
+snapshot() {
+	name=$1
+	repo=$2
+	path=$3
+	ref=$4
+	tmp=$(mktemp -d)
+	git clone --quiet "$repo" "$tmp"
+	git -C "$tmp" checkout --quiet "${ref:-HEAD}"
+	git -C "$tmp" rev-parse HEAD > "$name.commit"
+	tar -czf "$name.tar.gz" -C "$tmp/$path" .
+	rm -rf "$tmp"
+	echo "$name: $(cat "$name.commit")"
+}
+
+snapshot framework https://github.com/MrRobb/beacon-framework-v2 . "$1"
+snapshot model https://github.com/MrRobb/beacon-v2-Models BEACON-V2-draft4-Model "$2"
@@ -0,0 +1,40 @@
+use std::path::Path;
+
+/// Embeds the snapshots of the default framework and model (see `assets/snapshots/update.sh`), so that installed
+/// verifiers work offline. Builds without them still work, but `embedded://` locations fail at runtime.
+fn main() {
+	let snapshots_dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("assets").join("snapshots");
+	println!("cargo:rerun-if-changed={}", snapshots_dir.display());
+	let release = std::env::var("PROFILE").map_or(false, |profile| profile == "release");
+
+	let mut code = String::new();
+	for name in ["framework", "model"] {
+		let archive = snapshots_dir.join(format!("{}.tar.gz", name));
+		let archive = if archive.exists() {
+			format!("Some(include_bytes!({:?}))", archive)
+		}
+		else {
+			if release {
+				println!(
+					"cargo:warning=The {} snapshot {} is missing, run assets/snapshots/update.sh to embed it in the \
+					 release",
+					name,
+					archive.display()
+				);
+			}
+			String::from("None")
+		};
+		let version = std::fs::read_to_string(snapshots_dir.join(format!("{}.commit", name)))
+			.map_or_else(|_| String::from("None"), |commit| format!("Some({:?})", commit.trim()));
+		code.push_str(&format!(
+			"pub const {name}_ARCHIVE: Option<&[u8]> = {archive};\npub const {name}_VERSION: Option<&str> = \
+			 {version};\n",
+			name = name.to_uppercase(),
+			archive = archive,
+			version = version
+		));
+	}
+
+	let out_dir = std::env::var("OUT_DIR").unwrap();
+	std::fs::write(Path::new(&out_dir).join("snapshots.rs"), code).unwrap();
+}
@@ -1,44 +1,85 @@
+use std::collections::BTreeMap;
 use std::path::Path;
 
 use chrono::SubsecRound;
+use reqwest::blocking::Client;
 use url::Url;
 
+use crate::checks::{self, Check, Severity};
+use crate::consistency::{self, BeaconIdentity};
 use crate::error::VerifierError;
 use crate::framework::Framework;
-use crate::model::Model;
+use crate::handovers::Handovers;
+use crate::interface::Endpoint;
+use crate::model::{Entity, Model};
+use crate::negative;
 use crate::output::{BeaconOutput, EndpointReport, Output};
+use crate::profiles::Profile;
+use crate::resolver::LocalResolver;
+use crate::sampling::Sampling;
+use crate::schemas::BeaconSchemas;
+use crate::service_info;
+use crate::suites::Suite;
+use crate::waivers::Waivers;
 use crate::{utils, Json};
 
-use clap::Parser;
-
 pub struct Beacon {
+	client: Client,
 	name: String,
 	url: Url,
+	identity: BeaconIdentity,
+	info: Json,
 	model: Option<Model>,
 	framework: Framework,
+	discover: bool,
+	offline: bool,
+	sampling: Sampling,
+	skip_external_handovers: bool,
+	profile: Option<Profile>,
+	severities: BTreeMap<Check, Severity>,
+	waivers: Waivers,
+	suites: Vec<Suite>,
 }
 
 impl Beacon {
-	pub fn new(model: Option<Model>, framework: Framework, url: &Url) -> Result<Self, VerifierError> {
+	pub fn new(client: &Client, model: Option<Model>, framework: Framework, url: &Url) -> Result<Self, VerifierError> {
 		let mut info_url = url.clone();
 		info_url.set_path(Path::new(url.path()).join("info").to_str().unwrap_or(""));
 
-                // switch to builder to allow ignore self-signed ssl certs
-                let matches = crate::Args::parse();
-                let client = reqwest::blocking::Client::builder()
-                    .danger_accept_invalid_certs(matches.ssl_no_verify)
-                    .build()?;
-		let info: Json = client.get(&info_url.to_string()).send()?.json().unwrap();
+		let info: Json = client.get(info_url.to_string()).send()?.json().unwrap();
 		log::trace!("{}", info);
 
 		Ok(Self {
+			client: client.clone(),
 			name: Self::get_name(&info, url),
 			url: url.clone(),
+			identity: BeaconIdentity::new(&info),
+			info,
 			model,
 			framework,
+			discover: false,
+			offline: false,
+			sampling: Sampling::First(1),
+			skip_external_handovers: false,
+			profile: None,
+			severities: BTreeMap::new(),
+			waivers: Waivers::default(),
+			suites: Vec::new(),
 		})
 	}
 
+	/// Also validate the entry types of the beacon map that the model does not have
+	pub fn discover(mut self, discover: bool) -> Self {
+		self.discover = discover;
+		self
+	}
+
+	/// Do not download the advertised schemas that are not in the framework or the model
+	pub fn offline(mut self, offline: bool) -> Self {
+		self.offline = offline;
+		self
+	}
+
 	fn get_name(info: &Json, url: &Url) -> String {
 		let name_json = info.get("response").map_or_else(
 			|| {
@@ -72,12 +113,200 @@ impl Beacon {
 		name
 	}
 
-	fn validate_against_framework(&self, entity_name: &str, location: &str, schema: &Json) -> EndpointReport {
+	fn endpoint_url(&self, location: &str) -> Url {
 		let mut url = self.url.clone();
-		url.set_path(Path::new(self.url.path()).join(&location).to_str().unwrap_or(""));
-		let report = match utils::ping_url(&url) {
+		url.set_path(Path::new(self.url.path()).join(location).to_str().unwrap_or(""));
+		url
+	}
+
+	/// Schemas advertised in `/entry_types` and `/configuration`
+	fn advertised_schemas(&self, resolver: &LocalResolver) -> BeaconSchemas {
+		let responses = ["entry_types", "configuration"]
+			.iter()
+			.filter_map(|location| {
+				utils::ping_url(&self.client, &self.endpoint_url(location))
+					.map_err(|e| log::error!("{}", e))
+					.ok()
+			})
+			.collect::<Vec<_>>();
+		let client = Some(&self.client).filter(|_| !self.offline);
+		BeaconSchemas::load(client, &responses, &self.url, resolver)
+	}
+
+	/// Ids used to test the single entry and related endpoints
+	pub fn sampling(mut self, sampling: Sampling) -> Self {
+		self.sampling = sampling;
+		self
+	}
+
+	/// Do not request the handovers outside the host of the beacon
+	pub fn skip_external_handovers(mut self, skip_external_handovers: bool) -> Self {
+		self.skip_external_handovers = skip_external_handovers;
+		self
+	}
+
+	/// Conformance profile of the beacon, by default the one of `securityAttributes.defaultGranularity` in
+	/// `/configuration`
+	pub fn profile(mut self, profile: Option<Profile>) -> Self {
+		self.profile = profile;
+		self
+	}
+
+	/// Severity of the failures of the checks, instead of the default one
+	pub fn severities(mut self, severities: BTreeMap<Check, Severity>) -> Self {
+		self.severities = severities;
+		self
+	}
+
+	/// Known deviations of the beacon that do not fail the validation
+	pub fn waivers(mut self, waivers: Waivers) -> Self {
+		self.waivers = waivers;
+		self
+	}
+
+	/// Queries with known answers, run after the schema checks
+	pub fn suites(mut self, suites: Vec<Suite>) -> Self {
+		self.suites = suites;
+		self
+	}
+
+	/// Makes a url template of the beacon map relative to the root of the beacon, like the ones of the model
+	fn relative_url(&self, template: &str) -> String {
+		let origin = self.url.origin().ascii_serialization();
+		let root = format!("{}{}", origin, self.url.path().trim_end_matches('/'));
+		template
+			.strip_prefix(&root)
+			.map_or_else(|| template.to_string(), |path| format!("{}{}", origin, path))
+	}
+
+	/// Adds to the model the entry types of the beacon map that it does not have, with their advertised default
+	/// schema. Returns the reports of the entry types whose schema is not available.
+	fn discover_entry_types(&self, model: &mut Model, schemas: &BeaconSchemas) -> Vec<EndpointReport> {
+		let beacon_map = match utils::ping_url(&self.client, &self.endpoint_url("map")) {
+			Ok(beacon_map) => beacon_map,
+			Err(e) => {
+				log::error!("{}", e);
+				return Vec::new();
+			},
+		};
+
+		let mut reports = Vec::new();
+		let mut discovered = Vec::new();
+		let endpoint_sets = beacon_map["response"]["endpointSets"]
+			.as_object()
+			.cloned()
+			.unwrap_or_default();
+		for (key, endpoint_set) in endpoint_sets {
+			let mut endpoint: Endpoint = match serde_json::from_value(endpoint_set) {
+				Ok(endpoint) => endpoint,
+				Err(e) => {
+					log::error!("Skipping endpoint set {} of the beacon map: {}", key, e);
+					continue;
+				},
+			};
+			if model.entities.iter().any(|entity| entity.entry_type == endpoint.entry_type) {
+				continue;
+			}
+
+			let name = schemas
+				.entry_type(&endpoint.entry_type)
+				.and_then(|entry_type| entry_type.name.clone())
+				.unwrap_or_else(|| endpoint.entry_type.clone());
+			log::info!("Discovered entry type {} ({})", endpoint.entry_type, name);
+			match schemas.default_schema(&endpoint.entry_type) {
+				Ok(claimed) => {
+					endpoint.root_url = self.relative_url(&endpoint.root_url);
+					endpoint.single_entry_url = endpoint.single_entry_url.map(|url| self.relative_url(&url));
+					endpoint.filtering_terms_url =
+						endpoint.filtering_terms_url.map(|url| self.relative_url(&url));
+					for related in endpoint.endpoints.iter_mut().flat_map(BTreeMap::values_mut) {
+						related.url = self.relative_url(&related.url);
+					}
+					discovered.push(endpoint.entry_type.clone());
+					model.entities_names.insert(endpoint.entry_type.clone(), name);
+					model.add_entity(endpoint, claimed.schema, claimed.id_pointer);
+				},
+				Err(e) => {
+					log::error!("{}", e);
+					let url = Url::parse(&endpoint.root_url).unwrap_or_else(|_| self.url.clone());
+					reports.push(
+						EndpointReport::new(&name, &format!("{} schema", name), url)
+							.error(e)
+							.check(Check::EntryTypeSchema),
+					);
+				},
+			}
+		}
+
+		// Related endpoints can only be validated if their entry type is known
+		let known = model
+			.entities
+			.iter()
+			.map(|entity| entity.entry_type.clone())
+			.collect::<Vec<_>>();
+		for entity in model.entities.iter_mut().filter(|entity| discovered.contains(&entity.entry_type)) {
+			if let Some(related_endpoints) = &mut entity.related_endpoints {
+				related_endpoints.retain(|key, related| {
+					let is_known = known.contains(&related.returned_entry_type);
+					if !is_known {
+						log::warn!(
+							"Skipping related endpoint {} of {}, entry type {} is unknown",
+							key,
+							entity.name,
+							related.returned_entry_type
+						);
+					}
+					is_known
+				});
+			}
+		}
+
+		reports
+	}
+
+	/// Checks that the main endpoint of an entity gives the same answer with each granularity
+	fn validate_granularities(&self, entity: &Entity) -> Option<EndpointReport> {
+		let url = utils::expand_template(&entity.url, &[]).and_then(|url| Url::parse(&url).ok())?;
+		let url = utils::url_join(&self.url, &url);
+		log::info!("Validating granularities of {:?}", entity.name);
+		let report = EndpointReport::new(&entity.name, &format!("{} granularities", entity.name), url.clone())
+			.check(Check::Granularities);
+		Some(match consistency::check_granularities(&self.client, &url) {
+			Ok(()) => report.ok(None),
+			Err(e @ (VerifierError::InconsistentResponse(_) | VerifierError::RequestSummaryMismatch(_))) => {
+				report.error(e)
+			},
+			Err(e) => report.null(e),
+		})
+	}
+
+	/// Validates the GA4GH service-info and checks that it describes the same beacon as `/info`
+	fn validate_service_info(&self) -> EndpointReport {
+		let url = self.endpoint_url("service-info");
+		let report = EndpointReport::new("ServiceInfo", &self.name, url.clone());
+		let service_info = match utils::ping_url(&self.client, &url) {
+			Ok(service_info) => service_info,
+			Err(e) => return report.null(e),
+		};
+		let schema = utils::compile_schema(
+			&serde_json::from_str(service_info::SCHEMA).unwrap(),
+			&LocalResolver::default(),
+		);
+		match utils::valid_schema(&schema, &service_info).and_then(|_| service_info::check(&service_info, &self.info)) {
+			Ok(()) => report.ok(None),
+			Err(e) => report.error(e),
+		}
+	}
+
+	fn validate_against_framework(&self, entity_name: &str, location: &str, schema: &Json) -> EndpointReport {
+		let url = self.endpoint_url(location);
+		let report = match utils::ping_url(&self.client, &url) {
 			Ok(beacon_map_json) => {
-				let json_schema = match jsonschema::JSONSchema::options().with_meta_schemas().compile(schema) {
+				let json_schema = match jsonschema::JSONSchema::options()
+					.with_meta_schemas()
+					.with_resolver(self.framework.resolver.clone())
+					.compile(schema)
+				{
 					Ok(schema) => schema,
 					Err(e) => {
 						log::error!("{:?}", e);
@@ -85,7 +314,9 @@ impl Beacon {
 							.null(VerifierError::BadSchema);
 					},
 				};
-				match utils::valid_schema(&json_schema, &beacon_map_json) {
+				match utils::valid_schema(&json_schema, &beacon_map_json)
+					.and_then(|output| self.identity.check(&output).map(|()| output))
+				{
 					Ok(output) => EndpointReport::new(entity_name, &self.name, self.url.clone()).ok(Some(output)),
 					Err(e) => EndpointReport::new(entity_name, &self.name, self.url.clone()).error(e),
 				}
@@ -98,60 +329,150 @@ impl Beacon {
 		report.url(url)
 	}
 
-	pub fn validate(self) -> BeaconOutput {
+	pub fn validate(mut self) -> BeaconOutput {
 		let mut output = Output::new();
 
 		// Validate info
 		log::info!("Validating \"Info\"");
 		let report = self.validate_against_framework("Info", "info", &self.framework.info_json);
-		output.push(report);
+		output.push(report.check(Check::Info));
+		let report = EndpointReport::new("Info", "apiVersion", self.endpoint_url("info")).check(Check::ApiVersion);
+		let spec_version = match self.identity.spec_version() {
+			Ok(Some(spec_version)) => {
+				output.push(report.ok(None));
+				Some(spec_version)
+			},
+			Ok(None) => None,
+			Err(e) => {
+				output.push(report.error(e));
+				None
+			},
+		};
 
 		// Validate configuration
 		log::info!("Validating \"Configuration\"");
 		let report =
 			self.validate_against_framework("Configuration", "configuration", &self.framework.configuration_json);
-		output.push(report);
+		output.push(report.check(Check::Configuration));
 
 		// Validate beacon map
 		log::info!("Validating \"Beacon Map\"");
 		let report = self.validate_against_framework("BeaconMap", "map", &self.framework.beacon_map_json);
-		output.push(report);
+		output.push(report.check(Check::BeaconMap));
 
 		// Validate entry types
 		log::info!("Validating \"Entry Types\"");
 		let report = self.validate_against_framework("EntryTypes", "entry_types", &self.framework.entry_types_json);
-		output.push(report);
+		output.push(report.check(Check::EntryTypes));
+
+		// Validate the handovers of info
+		let mut handovers = Handovers::new(&self.client, &self.url, self.skip_external_handovers);
+		let report = EndpointReport::new("Info", "Handovers", self.endpoint_url("info")).check(Check::Handovers);
+		match handovers.check_response("Info", &self.info) {
+			Ok(0) => (),
+			Ok(_) => output.push(report.ok(None)),
+			Err(e) => output.push(report.error(e)),
+		}
+
+		// Validate service info
+		log::info!("Validating \"Service Info\"");
+		let report = self.validate_service_info();
+		output.push(report.check(Check::ServiceInfo));
 
 		// Validate endpoints configuration
 		// TODO: Validate OpenAPI 3.0
 
 		// Validate entities
-		if let Some(model) = self.model {
-			let boolean_json = utils::compile_schema(&self.framework.boolean_json);
-			let count_json = utils::compile_schema(&self.framework.count_json);
-			let result_sets_json = utils::compile_schema(&self.framework.result_sets_json);
-			let collections_json = utils::compile_schema(&self.framework.collections_json);
+		let model_source = self.model.as_ref().map(|model| model.source.clone());
+		let schemas = self
+			.model
+			.as_ref()
+			.map(|model| self.advertised_schemas(&model.resolver));
+		if let (Some(mut model), Some(schemas)) = (self.model.take(), schemas) {
+			if self.discover {
+				log::info!("Discovering entry types");
+				for report in self.discover_entry_types(&mut model, &schemas) {
+					output.push(report);
+				}
+			}
+			let responses = self.framework.response_schemas();
+			let error_json = (!self.framework.error_json.is_null())
+				.then(|| utils::compile_schema(&self.framework.error_json, &self.framework.resolver));
+			for entity in &model.entities {
+				if let Some(report) = self.validate_granularities(entity) {
+					output.push(report);
+				}
+				for report in negative::check_error_responses(&self.client, &self.url, entity, error_json.as_deref()) {
+					output.push(report);
+				}
+			}
+			for suite in &self.suites {
+				log::info!("Running test suite {:?}", suite.name);
+				for report in suite.run(&self.client, &self.url, &model.entities) {
+					output.push(report);
+				}
+			}
 			model
-				.endpoints(&self.url)
+				.endpoints(&self.client, &self.url, &self.sampling)
 				.into_iter()
-				.map(|endpoint| {
-					log::info!("Validating {:?}", endpoint.name);
-					endpoint.validate(
-						&self.url,
-						&boolean_json,
-						&count_json,
-						&result_sets_json,
-						&collections_json,
-					)
+				.flat_map(|endpoint| match endpoint {
+					Ok(endpoint) => {
+						log::info!("Validating {:?}", endpoint.name);
+						endpoint.validate(
+							&self.client,
+							&self.url,
+							&responses,
+							&schemas,
+							&self.identity,
+							&mut handovers,
+						)
+					},
+					Err(report) => vec![report],
 				})
 				.for_each(|report| output.push(report));
 		}
 
+		// Conformance profiles
+		let profile = self.profile.unwrap_or_else(|| {
+			utils::ping_url(&self.client, &self.endpoint_url("configuration"))
+				.ok()
+				.and_then(|configuration| Profile::from_configuration(&configuration))
+				.unwrap_or(Profile::Record)
+		});
+		log::info!("Conformance profile: {:?}", profile);
+		let mut entities = output.finish();
+		for report in entities.values_mut().flatten() {
+			report.requirement = report.check.map(|check| profile.requirement(check));
+		}
+
+		// Severities and waivers
+		let beacon = [
+			Some(self.url.as_str()),
+			Some(self.name.as_str()),
+			self.identity.id.as_deref(),
+		]
+		.into_iter()
+		.flatten()
+		.collect::<Vec<_>>();
+		BeaconOutput::judge(&mut entities, &beacon, &self.severities, &self.waivers);
+		let profiles = Profile::ALL
+			.iter()
+			.map(|profile| (*profile, profile.passes(&entities)))
+			.collect();
+
 		BeaconOutput {
 			name: self.name,
 			url: self.url,
 			last_updated: chrono::offset::Utc::now().naive_utc().round_subsecs(6),
-			entities: output.finish(),
+			model: model_source,
+			framework: Some(self.framework.source),
+			spec_version: spec_version.map(|spec_version| spec_version.to_string()),
+			release: None,
+			handovers: handovers.coverage(),
+			profile: Some(profile),
+			profiles,
+			requirements: checks::coverage(&entities),
+			entities,
 		}
 	}
 }
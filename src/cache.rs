@@ -0,0 +1,285 @@
+use std::fs::File;
+use std::path::PathBuf;
+
+use chrono::{Duration, NaiveDateTime, SubsecRound};
+use git2::Repository;
+use serde::{Deserialize, Serialize};
+
+use crate::error::VerifierError;
+use crate::source;
+
+/// On-disk cache of the cloned git repositories, one working copy per repository and reference
+#[derive(Debug, Clone)]
+pub struct Cache {
+	dir: PathBuf,
+	max_age: Duration,
+	offline: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CacheEntry {
+	pub key: String,
+	pub repository: String,
+	pub reference: Option<String>,
+	pub commit: Option<String>,
+	pub fetched_at: NaiveDateTime,
+}
+
+impl Cache {
+	pub fn new(dir: Option<PathBuf>, max_age_secs: i64, offline: bool) -> Self {
+		Self {
+			dir: dir.unwrap_or_else(Self::default_dir),
+			max_age: Duration::seconds(max_age_secs),
+			offline,
+		}
+	}
+
+	pub fn is_offline(&self) -> bool {
+		self.offline
+	}
+
+	/// `$XDG_CACHE_HOME/beacon-verifier` or `~/.cache/beacon-verifier`
+	fn default_dir() -> PathBuf {
+		std::env::var_os("XDG_CACHE_HOME")
+			.map(PathBuf::from)
+			.or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))
+			.unwrap_or_else(std::env::temp_dir)
+			.join("beacon-verifier")
+	}
+
+	/// Directory name of a repository and reference. Everything but ASCII alphanumerics, `-` and `_` is escaped as
+	/// `%XX`, so that different repositories or references never share a key and no key clashes with an entry file.
+	fn key(repo_url: &str, reference: Option<&str>) -> String {
+		let escape = |s: &str| {
+			s.bytes()
+				.map(|b| {
+					if b.is_ascii_alphanumeric() || b == b'-' || b == b'_' {
+						char::from(b).to_string()
+					}
+					else {
+						format!("%{:02X}", b)
+					}
+				})
+				.collect::<String>()
+		};
+		let repo_url = repo_url.split_once("://").map_or(repo_url, |(_, rest)| rest);
+		let key = escape(repo_url.trim_end_matches('/'));
+		match reference {
+			Some(reference) => format!("{}@{}", key, escape(reference)),
+			None => key,
+		}
+	}
+
+	fn entry_path(&self, key: &str) -> PathBuf {
+		self.dir.join(format!("{}.json", key))
+	}
+
+	/// Opens the cached working copy of a repository, cloning it if it is not cached yet and fetching it if it is
+	/// stale. Tags and commits never get stale.
+	pub fn repository(&self, repo_url: &str, reference: Option<&str>) -> Result<Repository, VerifierError> {
+		let key = Self::key(repo_url, reference);
+		let path = self.dir.join(&key);
+
+		if !path.exists() {
+			if self.offline {
+				return Err(VerifierError::NotCached(repo_url.to_string()));
+			}
+			log::debug!("Cloning {} into the cache ({:?})", repo_url, path);
+			std::fs::create_dir_all(&self.dir)?;
+			let repository = source::clone_repository(repo_url, &path)?;
+			self.save_entry(&key, repo_url, reference)?;
+			return Ok(repository);
+		}
+
+		log::debug!("Using cached repository {} ({:?})", repo_url, path);
+		let repository = Repository::open(&path)?;
+		let pinned = Self::pinned(&repository, reference, repo_url);
+		let stale = self.load_entry(&key).map_or(true, |entry| {
+			chrono::offset::Utc::now().naive_utc() - entry.fetched_at > self.max_age
+		});
+
+		if self.offline {
+			log::debug!("Offline mode, {} is not fetched", repo_url);
+		}
+		else if !pinned && stale {
+			Self::fetch(&repository)?;
+			self.save_entry(&key, repo_url, reference)?;
+		}
+
+		Ok(repository)
+	}
+
+	/// Tags and commits, which never change once cloned
+	fn pinned(repository: &Repository, reference: Option<&str>, repo_url: &str) -> bool {
+		matches!(
+			source::resolve_reference(repository, reference, repo_url),
+			Ok((_, false))
+		)
+	}
+
+	fn fetch(repository: &Repository) -> Result<(), VerifierError> {
+		log::debug!("Fetching {:?}", repository.path());
+		repository.find_remote("origin")?.fetch(
+			&["+refs/heads/*:refs/remotes/origin/*", "+refs/tags/*:refs/tags/*"],
+			Some(&mut source::fetch_options()?),
+			None,
+		)?;
+		Ok(())
+	}
+
+	fn load_entry(&self, key: &str) -> Option<CacheEntry> {
+		let file = File::open(self.entry_path(key)).ok()?;
+		let mut entry: CacheEntry = serde_json::from_reader(file).ok()?;
+		entry.commit = Repository::open(self.dir.join(key))
+			.and_then(|repository| {
+				let commit = repository.head()?.peel_to_commit()?;
+				Ok(commit.id().to_string())
+			})
+			.ok();
+		Some(entry)
+	}
+
+	fn save_entry(&self, key: &str, repo_url: &str, reference: Option<&str>) -> Result<(), VerifierError> {
+		let entry = CacheEntry {
+			key: key.to_string(),
+			repository: repo_url.to_string(),
+			reference: reference.map(String::from),
+			commit: None,
+			fetched_at: chrono::offset::Utc::now().naive_utc().round_subsecs(0),
+		};
+		serde_json::to_writer_pretty(File::create(self.entry_path(key))?, &entry)?;
+		Ok(())
+	}
+
+	pub fn entries(&self) -> Vec<CacheEntry> {
+		std::fs::read_dir(&self.dir)
+			.map(|entries| {
+				entries
+					.flatten()
+					.filter(|entry| entry.path().is_dir())
+					.filter_map(|entry| self.load_entry(&entry.file_name().to_string_lossy()))
+					.collect()
+			})
+			.unwrap_or_default()
+	}
+
+	/// Removes the entries that are stale (or all of them) and returns them. Tags and commits never get stale.
+	pub fn prune(&self, all: bool) -> Result<Vec<CacheEntry>, VerifierError> {
+		let now = chrono::offset::Utc::now().naive_utc();
+		let mut pruned = Vec::new();
+		for entry in self.entries() {
+			let stale = || {
+				now - entry.fetched_at > self.max_age
+					&& !Repository::open(self.dir.join(&entry.key)).map_or(false, |repository| {
+						Self::pinned(&repository, entry.reference.as_deref(), &entry.repository)
+					})
+			};
+			if all || stale() {
+				log::info!("Removing {} from the cache", entry.key);
+				std::fs::remove_dir_all(self.dir.join(&entry.key))?;
+				std::fs::remove_file(self.entry_path(&entry.key))?;
+				pruned.push(entry);
+			}
+		}
+		Ok(pruned)
+	}
+
+	/// Fetches all the entries again and returns them
+	pub fn refresh(&self) -> Result<Vec<CacheEntry>, VerifierError> {
+		if self.offline {
+			return Err(VerifierError::Offline);
+		}
+		for entry in self.entries() {
+			log::info!("Refreshing {}", entry.key);
+			Self::fetch(&Repository::open(self.dir.join(&entry.key))?)?;
+			self.save_entry(&entry.key, &entry.repository, entry.reference.as_deref())?;
+		}
+		Ok(self.entries())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+
+	use std::path::Path;
+
+	use chrono::Duration;
+	use git2::{Repository, Signature};
+	use url::Url;
+
+	use crate::cache::Cache;
+
+	/// Repository with one commit, tagged `v1`
+	fn repository(dir: &Path) -> String {
+		let repository = Repository::init(dir).unwrap();
+		std::fs::write(dir.join("beaconMap.json"), "{}").unwrap();
+		let mut index = repository.index().unwrap();
+		index.add_path(Path::new("beaconMap.json")).unwrap();
+		let tree = repository.find_tree(index.write_tree().unwrap()).unwrap();
+		let signature = Signature::now("Beacon", "beacon@example.org").unwrap();
+		let commit = repository
+			.commit(Some("HEAD"), &signature, &signature, "Model", &tree, &[])
+			.unwrap();
+		repository
+			.tag_lightweight("v1", &repository.find_object(commit, None).unwrap(), false)
+			.unwrap();
+		Url::from_directory_path(dir).unwrap().to_string()
+	}
+
+	#[test]
+	fn test_key() {
+		assert_eq!(
+			Cache::key("https://github.com/ga4gh-beacon/beacon-v2", Some("v2.0.0")),
+			"github%2Ecom%2Fga4gh-beacon%2Fbeacon-v2@v2%2E0%2E0"
+		);
+		assert_ne!(
+			Cache::key("https://github.com/org/a_b", None),
+			Cache::key("https://github.com/org/a/b", None)
+		);
+		assert_ne!(
+			Cache::key("https://github.com/org/repo", Some("default")),
+			Cache::key("https://github.com/org/repo", None)
+		);
+		assert_ne!(
+			Cache::key("https://github.com/org/repo", Some("feature/a")),
+			Cache::key("https://github.com/org/repo", Some("feature_a"))
+		);
+	}
+
+	#[test]
+	fn test_prune() {
+		let origin = tempfile::tempdir().unwrap();
+		let repo_url = repository(origin.path());
+		let dir = tempfile::tempdir().unwrap();
+		let cache = Cache::new(Some(dir.path().to_path_buf()), 3600, false);
+		cache.repository(&repo_url, None).unwrap();
+		cache.repository(&repo_url, Some("v1")).unwrap();
+		let mut entries = cache.entries();
+		entries.sort_by_key(|entry| entry.key.clone());
+		assert_eq!(
+			entries
+				.iter()
+				.map(|entry| entry.reference.as_deref())
+				.collect::<Vec<_>>(),
+			vec![None, Some("v1")]
+		);
+		assert!(entries.iter().all(|entry| entry.commit.is_some()));
+
+		// Both are fresh
+		assert!(cache.prune(false).unwrap().is_empty());
+
+		// Only the branch gets stale
+		let cache = Cache {
+			max_age: Duration::seconds(-1),
+			..cache
+		};
+		let pruned = cache.prune(false).unwrap();
+		assert_eq!(pruned.len(), 1);
+		assert_eq!(pruned[0].reference, None);
+		assert!(!dir.path().join(&pruned[0].key).exists());
+		assert_eq!(cache.entries().len(), 1);
+
+		assert_eq!(cache.prune(true).unwrap().len(), 1);
+		assert!(cache.entries().is_empty());
+	}
+}
@@ -0,0 +1,347 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::output::EndpointOutput;
+use crate::profiles::{Profile, Requirement};
+
+/// Kind of check a report comes from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Check {
+	/// `/info` validates against the framework
+	Info,
+	/// `apiVersion` of `/info` is a semantic version
+	ApiVersion,
+	/// `/configuration` validates against the framework
+	Configuration,
+	/// `/map` validates against the framework
+	BeaconMap,
+	/// `/entry_types` validates against the framework
+	EntryTypes,
+	/// `/service-info` validates against the GA4GH service-info schema and matches `/info`
+	ServiceInfo,
+	/// Handovers of `/info` and of the responses are valid and respond
+	Handovers,
+	/// Schema advertised for a discovered entry type is available
+	EntryTypeSchema,
+	/// Ids can be extracted from the records of an entity
+	Ids,
+	/// Main endpoint of an entity
+	Endpoint,
+	/// Single entry endpoint of an entity
+	SingleEntry,
+	/// Endpoint of the records of an entity related with another one
+	RelatedEndpoint,
+	/// Counts of a response agree with each other and with its results
+	Consistency,
+	/// `receivedRequestSummary` of a response echoes the request
+	RequestSummary,
+	/// `beaconId` and `apiVersion` of a response are the ones of `/info`
+	Identity,
+	/// Records of a response are the requested ones
+	Integrity,
+	/// Answers of each granularity agree
+	Granularities,
+	/// Bad requests are rejected with an error response
+	ErrorResponse,
+}
+
+/// Severity of a failed check
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+	Info,
+	Warning,
+	Error,
+}
+
+/// Level of a requirement of the specification (RFC 2119)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum Level {
+	Must,
+	Should,
+}
+
+/// Requirement of the specification a check enforces
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SpecReference {
+	pub level: Level,
+	/// Schemas of the framework that define the requirement, relative to the root of the framework used (with a JSON
+	/// pointer to the definition), or urls of external specifications
+	pub locations: Vec<String>,
+}
+
+/// Whether a requirement was tested and passed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CoverageStatus {
+	Passed,
+	Failed,
+	/// None of its checks could be run (e.g. the endpoint did not respond) or applies to the beacon
+	NotTestable,
+}
+
+/// Results of the checks of a requirement
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RequirementCoverage {
+	#[serde(flatten)]
+	pub reference: SpecReference,
+	pub check: Check,
+	pub status: CoverageStatus,
+	pub passed: usize,
+	pub failed: usize,
+	pub not_testable: usize,
+}
+
+impl Check {
+	pub const ALL: [Self; 18] = [
+		Self::Info,
+		Self::ApiVersion,
+		Self::Configuration,
+		Self::BeaconMap,
+		Self::EntryTypes,
+		Self::ServiceInfo,
+		Self::Handovers,
+		Self::EntryTypeSchema,
+		Self::Ids,
+		Self::Endpoint,
+		Self::SingleEntry,
+		Self::RelatedEndpoint,
+		Self::Consistency,
+		Self::RequestSummary,
+		Self::Identity,
+		Self::Integrity,
+		Self::Granularities,
+		Self::ErrorResponse,
+	];
+
+	/// Severity of a failure by default: warnings for SHOULD requirements and errors for MUST ones
+	pub fn severity(self) -> Severity {
+		match self.reference().level {
+			Level::Must => Severity::Error,
+			Level::Should => Severity::Warning,
+		}
+	}
+
+	/// Locations of the requirement in the specification, its level and the most limited profile it applies to
+	fn spec(self) -> (&'static [&'static str], Level, Profile) {
+		match self {
+			Self::Info => (&["responses/beaconInfoResponse.json"], Level::Must, Profile::Boolean),
+			Self::ApiVersion => (
+				&["common/beaconCommonComponents.json#/definitions/ApiVersion"],
+				Level::Should,
+				Profile::Boolean,
+			),
+			Self::Configuration => (
+				&["responses/beaconConfigurationResponse.json"],
+				Level::Must,
+				Profile::Boolean,
+			),
+			Self::BeaconMap => (&["responses/beaconMapResponse.json"], Level::Must, Profile::Boolean),
+			Self::EntryTypes => (
+				&["responses/beaconEntryTypesResponse.json"],
+				Level::Must,
+				Profile::Boolean,
+			),
+			Self::ServiceInfo => (
+				&["https://github.com/ga4gh-discovery/ga4gh-service-info"],
+				Level::Should,
+				Profile::Boolean,
+			),
+			Self::Handovers => (
+				&["common/beaconCommonComponents.json#/definitions/Handover"],
+				Level::Should,
+				Profile::Boolean,
+			),
+			Self::EntryTypeSchema => (
+				&["configuration/beaconConfigurationSchema.json#/definitions/EntryType"],
+				Level::Must,
+				Profile::Record,
+			),
+			Self::Ids => (
+				&["configuration/beaconMapSchema.json#/definitions/Endpoint"],
+				Level::Must,
+				Profile::Record,
+			),
+			Self::Endpoint => (
+				&[
+					"responses/beaconBooleanResponse.json",
+					"responses/beaconCountResponse.json",
+					"responses/beaconResultsetsResponse.json",
+					"responses/beaconCollectionsResponse.json",
+				],
+				Level::Must,
+				Profile::Boolean,
+			),
+			Self::SingleEntry => (
+				&["configuration/beaconMapSchema.json#/definitions/Endpoint"],
+				Level::Must,
+				Profile::Record,
+			),
+			Self::RelatedEndpoint => (
+				&["configuration/beaconMapSchema.json#/definitions/RelatedEndpoint"],
+				Level::Must,
+				Profile::Record,
+			),
+			Self::Consistency => (
+				&["responses/sections/beaconResponseSummary.json"],
+				Level::Must,
+				Profile::Boolean,
+			),
+			Self::RequestSummary => (
+				&["responses/sections/beaconReceivedRequestSummary.json"],
+				Level::Must,
+				Profile::Boolean,
+			),
+			Self::Identity => (
+				&["responses/sections/beaconResponseMeta.json"],
+				Level::Must,
+				Profile::Boolean,
+			),
+			Self::Integrity => (
+				&["configuration/beaconMapSchema.json#/definitions/Endpoint"],
+				Level::Must,
+				Profile::Record,
+			),
+			Self::Granularities => (
+				&["common/beaconCommonComponents.json#/definitions/Granularity"],
+				Level::Should,
+				Profile::Boolean,
+			),
+			Self::ErrorResponse => (&["responses/beaconErrorResponse.json"], Level::Should, Profile::Boolean),
+		}
+	}
+
+	pub fn reference(self) -> SpecReference {
+		let (locations, level, _) = self.spec();
+		SpecReference {
+			level,
+			locations: locations.iter().map(|location| (*location).to_string()).collect(),
+		}
+	}
+
+	/// Requirement of the check in a profile: MUST requirements are required and SHOULD requirements optional, in the
+	/// profiles that return the information the check needs
+	pub fn requirement(self, profile: Profile) -> Requirement {
+		match self.spec() {
+			(_, _, applies_from) if profile < applies_from => Requirement::NotApplicable,
+			(_, Level::Must, _) => Requirement::Required,
+			(_, Level::Should, _) => Requirement::Optional,
+		}
+	}
+}
+
+/// Coverage of the requirements of every check by the reports of a beacon. The reports of the checks that do not
+/// apply to the profile of the beacon are not testable.
+pub fn coverage(entities: &BTreeMap<String, Vec<EndpointOutput>>) -> Vec<RequirementCoverage> {
+	Check::ALL
+		.iter()
+		.map(|check| {
+			let (mut passed, mut failed, mut not_testable) = (0, 0, 0);
+			for report in entities
+				.values()
+				.flatten()
+				.filter(|report| report.check == Some(*check))
+			{
+				match report.valid {
+					_ if report.requirement == Some(Requirement::NotApplicable) => not_testable += 1,
+					Some(true) => passed += 1,
+					Some(false) => failed += 1,
+					None => not_testable += 1,
+				}
+			}
+			let status = if failed > 0 {
+				CoverageStatus::Failed
+			}
+			else if passed > 0 {
+				CoverageStatus::Passed
+			}
+			else {
+				CoverageStatus::NotTestable
+			};
+			RequirementCoverage {
+				reference: check.reference(),
+				check: *check,
+				status,
+				passed,
+				failed,
+				not_testable,
+			}
+		})
+		.collect()
+}
+
+#[cfg(test)]
+mod tests {
+
+	use std::collections::BTreeMap;
+
+	use url::Url;
+
+	use crate::checks::{coverage, Check, CoverageStatus, Level, Severity};
+	use crate::output::EndpointOutput;
+	use crate::profiles::{Profile, Requirement};
+
+	#[test]
+	fn test_requirement() {
+		for check in Check::ALL {
+			let required = check.reference().level == Level::Must;
+			assert_eq!(check.severity() == Severity::Error, required);
+			assert_eq!(check.requirement(Profile::Record) == Requirement::Required, required);
+		}
+		assert_eq!(Check::Handovers.requirement(Profile::Boolean), Requirement::Optional);
+		assert_eq!(Check::Ids.requirement(Profile::Aggregated), Requirement::NotApplicable);
+		assert_eq!(
+			Check::Endpoint.reference().locations[0],
+			"responses/beaconBooleanResponse.json"
+		);
+	}
+
+	#[test]
+	fn test_coverage() {
+		let report = |check, valid, requirement| EndpointOutput {
+			name: String::from("Individuals"),
+			url: Url::parse("https://example.org/individuals").unwrap(),
+			valid,
+			error: None,
+			schema: None,
+			template: None,
+			check: Some(check),
+			reference: None,
+			requirement: Some(requirement),
+			severity: None,
+			waiver: None,
+			suite: None,
+			errors: Vec::new(),
+		};
+		let mut entities = BTreeMap::new();
+		entities.insert(
+			String::from("Individuals"),
+			vec![
+				report(Check::Endpoint, Some(true), Requirement::Required),
+				report(Check::SingleEntry, Some(false), Requirement::NotApplicable),
+				report(Check::Granularities, Some(true), Requirement::Required),
+				report(Check::Granularities, Some(false), Requirement::Required),
+			],
+		);
+
+		let coverage = coverage(&entities);
+		let status = |check| coverage.iter().find(|coverage| coverage.check == check).unwrap().status;
+		assert_eq!(coverage.len(), Check::ALL.len());
+		assert_eq!(status(Check::Endpoint), CoverageStatus::Passed);
+		assert_eq!(status(Check::SingleEntry), CoverageStatus::NotTestable);
+		assert_eq!(status(Check::Granularities), CoverageStatus::Failed);
+		assert_eq!(status(Check::Info), CoverageStatus::NotTestable);
+
+		let counts = |check| {
+			let coverage = coverage.iter().find(|coverage| coverage.check == check).unwrap();
+			(coverage.passed, coverage.failed, coverage.not_testable)
+		};
+		assert_eq!(counts(Check::Endpoint), (1, 0, 0));
+		assert_eq!(counts(Check::SingleEntry), (0, 0, 1));
+		assert_eq!(counts(Check::Granularities), (1, 1, 0));
+		assert_eq!(counts(Check::Info), (0, 0, 0));
+	}
+}
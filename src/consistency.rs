@@ -0,0 +1,375 @@
+use reqwest::blocking::Client;
+use url::Url;
+
+use crate::error::VerifierError;
+use crate::interface::Granularity;
+use crate::{utils, Json};
+
+/// Checks that the counts of a response agree with each other: `exists` with `numTotalResults`, `numTotalResults`
+/// with the sum of the `resultsCount` and each `resultsCount` with its results (unless they are paginated)
+pub fn check_response(response: &Json) -> Result<(), VerifierError> {
+	let mut errors = Vec::new();
+
+	let exists = response["responseSummary"]["exists"].as_bool();
+	let total = response["responseSummary"]["numTotalResults"].as_u64();
+	if let (Some(exists), Some(total)) = (exists, total) {
+		if exists != (total > 0) {
+			errors.push(format!("exists is {} but numTotalResults is {}", exists, total));
+		}
+	}
+
+	let result_sets = response["response"]["resultSets"]
+		.as_array()
+		.cloned()
+		.unwrap_or_default();
+	let counts = result_sets
+		.iter()
+		.map(|result_set| result_set["resultsCount"].as_u64())
+		.collect::<Option<Vec<_>>>();
+	if let (Some(total), Some(counts)) = (total, counts) {
+		let sum = counts.iter().sum::<u64>();
+		if !counts.is_empty() && sum != total {
+			errors.push(format!(
+				"numTotalResults is {} but the sum of resultsCount is {}",
+				total, sum
+			));
+		}
+	}
+
+	let limit = response["meta"]["receivedRequestSummary"]["pagination"]["limit"]
+		.as_u64()
+		.unwrap_or(0);
+	for (i, result_set) in result_sets.iter().enumerate() {
+		let count = match result_set["resultsCount"].as_u64() {
+			Some(count) => count,
+			None => continue,
+		};
+		if let Some(exists) = result_set["exists"].as_bool() {
+			if exists != (count > 0) {
+				errors.push(format!(
+					"resultSets/{} exists is {} but its resultsCount is {}",
+					i, exists, count
+				));
+			}
+		}
+		let paginated = limit > 0 && count > limit;
+		if let Some(results) = result_set["results"].as_array() {
+			if !paginated && count != results.len() as u64 {
+				errors.push(format!(
+					"resultSets/{} resultsCount is {} but it has {} results",
+					i,
+					count,
+					results.len()
+				));
+			}
+		}
+	}
+
+	if errors.is_empty() {
+		Ok(())
+	}
+	else {
+		Err(VerifierError::InconsistentResponse(errors.join("\n")))
+	}
+}
+
+/// Requests the same url with each granularity and checks that the answers agree
+pub fn check_granularities(client: &Client, url: &Url) -> Result<(), VerifierError> {
+	let mut answers = Vec::new();
+	for granularity in [Granularity::Boolean, Granularity::Count, Granularity::Record] {
+		let requested = serde_json::to_value(granularity)?;
+		let mut granularity_url = url.clone();
+		granularity_url
+			.query_pairs_mut()
+			.append_pair("requestedGranularity", requested.as_str().unwrap_or_default());
+		let response = utils::ping_url(client, &granularity_url)?;
+		answers.push((
+			requested,
+			response["responseSummary"]["exists"].as_bool(),
+			response["responseSummary"]["numTotalResults"].as_u64(),
+		));
+	}
+
+	let disagree = |values: Vec<String>| values.windows(2).any(|pair| pair[0] != pair[1]);
+	let exists = answers
+		.iter()
+		.filter_map(|(_, exists, _)| exists.map(|exists| exists.to_string()))
+		.collect();
+	let totals = answers
+		.iter()
+		.filter_map(|(_, _, total)| total.map(|total| total.to_string()))
+		.collect();
+	if disagree(exists) || disagree(totals) {
+		let answers = answers
+			.iter()
+			.map(|(granularity, exists, total)| {
+				let exists = exists.map(|exists| format!(" exists {}", exists));
+				let total = total.map(|total| format!(" numTotalResults {}", total));
+				format!(
+					"{}:{}{}",
+					granularity.as_str().unwrap_or_default(),
+					exists.unwrap_or_default(),
+					total.unwrap_or_default()
+				)
+			})
+			.collect::<Vec<_>>();
+		return Err(VerifierError::InconsistentResponse(format!(
+			"The answers of each granularity disagree ({})",
+			answers.join(", ")
+		)));
+	}
+	Ok(())
+}
+
+/// Id and api version of a beacon in `/info`, which every response must repeat in its `meta`
+#[derive(Debug, Clone, Default)]
+pub struct BeaconIdentity {
+	pub id: Option<String>,
+	pub api_version: Option<String>,
+}
+
+impl BeaconIdentity {
+	pub fn new(info: &Json) -> Self {
+		let field = |value: &Json| value.as_str().map(String::from);
+		Self {
+			id: field(&info["response"]["id"]).or_else(|| field(&info["meta"]["beaconId"])),
+			api_version: field(&info["meta"]["apiVersion"]).or_else(|| field(&info["response"]["apiVersion"])),
+		}
+	}
+
+	/// Beacon specification version claimed by the api version (e.g. `2.0.0` for `v2.0.0`)
+	pub fn spec_version(&self) -> Result<Option<semver::Version>, VerifierError> {
+		self.api_version
+			.as_ref()
+			.map(|api_version| {
+				semver::Version::parse(api_version.trim_start_matches('v'))
+					.map_err(|e| VerifierError::BadApiVersion(api_version.clone(), e.to_string()))
+			})
+			.transpose()
+	}
+
+	/// Checks that `meta.beaconId` and `meta.apiVersion` of a response are the ones of `/info`
+	pub fn check(&self, response: &Json) -> Result<(), VerifierError> {
+		let mut errors = Vec::new();
+		for (field, expected) in [("beaconId", &self.id), ("apiVersion", &self.api_version)] {
+			let returned = response["meta"][field].as_str();
+			match (expected, returned) {
+				(Some(expected), Some(returned)) if expected != returned => {
+					errors.push(format!(
+						"meta.{} is {} but it is {} in /info",
+						field, returned, expected
+					));
+				},
+				(Some(expected), None) => {
+					errors.push(format!("meta.{} is missing (it is {} in /info)", field, expected))
+				},
+				_ => (),
+			}
+		}
+
+		if errors.is_empty() {
+			Ok(())
+		}
+		else {
+			Err(VerifierError::IdentityMismatch(errors.join("\n")))
+		}
+	}
+}
+
+/// Query parameters with their own field in `meta.receivedRequestSummary`, the rest go to `requestParameters`
+const SUMMARY_PARAMETERS: [&str; 7] = [
+	"apiVersion",
+	"requestedSchema",
+	"filters",
+	"skip",
+	"limit",
+	"requestedGranularity",
+	"includeResultsetResponses",
+];
+
+/// Checks that `meta.receivedRequestSummary` echoes the query parameters of the request sent to `url`, and the
+/// `apiVersion` and `requestedGranularity` used by default when they were not sent
+pub fn check_request_summary(url: &Url, response: &Json) -> Result<(), VerifierError> {
+	let summary = &response["meta"]["receivedRequestSummary"];
+	let mut errors = Vec::new();
+	let as_string = |value: &Json| value.as_str().map_or_else(|| value.to_string(), String::from);
+
+	for (key, sent) in url.query_pairs() {
+		let echoed = match key.as_ref() {
+			"requestedSchema" => summary["requestedSchemas"]
+				.as_array()
+				.into_iter()
+				.flatten()
+				.map(|schema| schema.get("schema").unwrap_or(schema))
+				.find(|schema| as_string(schema) == sent)
+				.cloned()
+				.unwrap_or(Json::Null),
+			"filters" => {
+				let echoed = summary["filters"]
+					.as_array()
+					.into_iter()
+					.flatten()
+					.map(|filter| as_string(filter.get("id").unwrap_or(filter)))
+					.collect::<Vec<_>>();
+				let missing = sent
+					.split(',')
+					.filter(|filter| !echoed.iter().any(|echoed| echoed == filter))
+					.collect::<Vec<_>>();
+				if !missing.is_empty() {
+					errors.push(format!("filters {} were sent but not echoed", missing.join(", ")));
+				}
+				continue;
+			},
+			"skip" | "limit" => summary["pagination"][key.as_ref()].clone(),
+			key if SUMMARY_PARAMETERS.contains(&key) => summary[key].clone(),
+			key => match summary["requestParameters"].get(key) {
+				Some(echoed) => echoed.clone(),
+				// Beacons do not have to echo the parameters they do not support
+				None => continue,
+			},
+		};
+		if echoed.is_null() {
+			errors.push(format!("{} {} was sent but not echoed", key, sent));
+		}
+		else if as_string(&echoed) != sent {
+			errors.push(format!("{} {} was sent but {} was echoed", key, sent, echoed));
+		}
+	}
+
+	let not_sent = |parameter| !url.query_pairs().any(|(key, _)| key == parameter);
+	if not_sent("apiVersion") && summary["apiVersion"].as_str().is_none() {
+		errors.push(String::from("apiVersion was not sent and the default was not echoed"));
+	}
+	if not_sent("requestedGranularity") {
+		let echoed = &summary["requestedGranularity"];
+		if echoed.is_null() {
+			errors.push(String::from(
+				"requestedGranularity was not sent and the default was not echoed",
+			));
+		}
+		else if serde_json::from_value::<Granularity>(echoed.clone()).is_err() {
+			errors.push(format!(
+				"requestedGranularity was not sent and {} was echoed, which is not a granularity",
+				echoed
+			));
+		}
+	}
+
+	if errors.is_empty() {
+		Ok(())
+	}
+	else {
+		Err(VerifierError::RequestSummaryMismatch(errors.join("\n")))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+
+	use serde_json::json;
+	use url::Url;
+
+	use crate::consistency::{check_request_summary, check_response, BeaconIdentity};
+
+	#[test]
+	fn test_check_response() {
+		let response = |exists, total, count, results: Vec<u64>| {
+			json!({
+				"meta": {"receivedRequestSummary": {"pagination": {"skip": 0, "limit": 2}}},
+				"responseSummary": {"exists": exists, "numTotalResults": total},
+				"response": {"resultSets": [{"exists": exists, "resultsCount": count, "results": results}]}
+			})
+		};
+
+		assert!(check_response(&response(true, 1, 1, vec![1])).is_ok());
+		assert!(check_response(&response(false, 0, 0, vec![])).is_ok());
+		// Paginated
+		assert!(check_response(&response(true, 3, 3, vec![1, 2])).is_ok());
+		assert!(check_response(&response(true, 0, 0, vec![])).is_err());
+		assert!(check_response(&response(true, 2, 1, vec![1])).is_err());
+		assert!(check_response(&response(true, 2, 2, vec![1])).is_err());
+		assert!(check_response(&json!({"meta": {}, "responseSummary": {"exists": true}})).is_ok());
+	}
+
+	#[test]
+	fn test_check_request_summary() {
+		let url = Url::parse("https://example.org/individuals?skip=10&limit=5&filters=NCIT:C20197,age&requestedGranularity=count&custom=1").unwrap();
+		let response = |granularity, filters| {
+			json!({"meta": {"receivedRequestSummary": {
+				"apiVersion": "v2.0.0",
+				"requestedSchemas": [],
+				"filters": filters,
+				"pagination": {"skip": 10, "limit": 5},
+				"requestedGranularity": granularity
+			}}})
+		};
+
+		assert!(check_request_summary(&url, &response("count", json!(["NCIT:C20197", {"id": "age"}]))).is_ok());
+		assert!(check_request_summary(&url, &response("record", json!(["NCIT:C20197", "age"]))).is_err());
+		assert!(check_request_summary(&url, &response("count", json!(["NCIT:C20197"]))).is_err());
+		let mut echoed = response("count", json!(["NCIT:C20197", "age"]));
+		echoed["meta"]["receivedRequestSummary"]["requestParameters"] = json!({"custom": 2});
+		assert!(check_request_summary(&url, &echoed).is_err());
+	}
+
+	#[test]
+	fn test_check_request_summary_defaults() {
+		let url = Url::parse("https://example.org/individuals").unwrap();
+		let response = |summary| json!({"meta": {"receivedRequestSummary": summary}});
+
+		assert!(check_request_summary(
+			&url,
+			&response(json!({"apiVersion": "v2.0.0", "requestedGranularity": "record"}))
+		)
+		.is_ok());
+		assert_eq!(
+			check_request_summary(&url, &response(json!({"requestedGranularity": "record"})))
+				.unwrap_err()
+				.to_string(),
+			"meta.receivedRequestSummary does not match the request: apiVersion was not sent and the default was not \
+			 echoed"
+		);
+		assert_eq!(
+			check_request_summary(
+				&url,
+				&response(json!({"apiVersion": "v2.0.0", "requestedGranularity": "all"}))
+			)
+			.unwrap_err()
+			.to_string(),
+			"meta.receivedRequestSummary does not match the request: requestedGranularity was not sent and \"all\" \
+			 was echoed, which is not a granularity"
+		);
+		assert_eq!(
+			check_request_summary(&url, &response(json!({"apiVersion": "v2.0.0"})))
+				.unwrap_err()
+				.to_string(),
+			"meta.receivedRequestSummary does not match the request: requestedGranularity was not sent and the \
+			 default was not echoed"
+		);
+	}
+
+	#[test]
+	fn test_beacon_identity() {
+		let identity = BeaconIdentity::new(&json!({
+			"meta": {"beaconId": "org.example.beacon", "apiVersion": "v2.0.0"},
+			"response": {"id": "org.example.beacon", "name": "Beacon"}
+		}));
+		assert_eq!(identity.spec_version().unwrap(), Some(semver::Version::new(2, 0, 0)));
+		assert!(BeaconIdentity {
+			id: None,
+			api_version: Some("v2".into())
+		}
+		.spec_version()
+		.is_err());
+
+		assert!(identity
+			.check(&json!({"meta": {"beaconId": "org.example.beacon", "apiVersion": "v2.0.0"}}))
+			.is_ok());
+		assert!(identity
+			.check(&json!({"meta": {"beaconId": "org.example.other", "apiVersion": "v2.0.0"}}))
+			.is_err());
+		assert!(identity
+			.check(&json!({"meta": {"beaconId": "org.example.beacon"}}))
+			.is_err());
+	}
+}
@@ -0,0 +1,228 @@
+use std::collections::BTreeMap;
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+
+use chrono::SubsecRound;
+use url::Url;
+
+use crate::error::VerifierError;
+use crate::model::{Entity, Model};
+use crate::output::{BeaconOutput, EndpointReport, Output};
+use crate::{utils, Json};
+
+/// Records of a file with their location (line or JSON pointer), or the error parsing them
+type Records = Vec<(String, Result<Json, VerifierError>)>;
+
+pub struct DataFiles {
+	model: Model,
+	files: Vec<PathBuf>,
+}
+
+impl DataFiles {
+	pub fn new(model: Model, files: Vec<PathBuf>) -> Self {
+		Self { model, files }
+	}
+
+	fn entity(&self, path: &Path) -> Option<&Entity> {
+		let stem = path.file_stem()?.to_string_lossy().to_lowercase();
+		self.model.entities.iter().find(|entity| {
+			let root_name = utils::expand_template(&entity.url, &[])
+				.and_then(|url| Url::parse(&url).ok())
+				.and_then(|url| {
+					Path::new(url.path())
+						.file_name()
+						.map(|name| name.to_string_lossy().to_lowercase())
+				});
+			entity.entry_type.to_lowercase() == stem
+				|| entity.name.to_lowercase() == stem
+				|| root_name.as_deref() == Some(stem.as_str())
+		})
+	}
+
+	/// Records of a JSON file (an array or a single record) or a JSON Lines file, with their location in the file.
+	/// Each line of a JSON Lines file is parsed on its own, so that a bad line does not hide the other records.
+	fn read_records(path: &Path) -> Result<Records, VerifierError> {
+		let contents = std::fs::read_to_string(path)?;
+		let bad_file = |e: serde_json::Error| VerifierError::BadDataFile(path.to_path_buf(), e.to_string());
+
+		match path.extension().and_then(OsStr::to_str) {
+			Some("jsonl" | "ndjson") => Ok(contents
+				.lines()
+				.enumerate()
+				.filter(|(_, line)| !line.trim().is_empty())
+				.map(|(i, line)| (format!("L{}", i + 1), serde_json::from_str(line).map_err(bad_file)))
+				.collect()),
+			_ => match serde_json::from_str(&contents).map_err(bad_file)? {
+				Json::Array(records) => Ok(records
+					.into_iter()
+					.enumerate()
+					.map(|(i, record)| (format!("/{}", i), Ok(record)))
+					.collect()),
+				record => Ok(vec![(String::new(), Ok(record))]),
+			},
+		}
+	}
+
+	fn validate_file(&self, path: &Path) -> Vec<EndpointReport> {
+		let file_name = path
+			.file_name()
+			.map_or_else(String::new, |f| f.to_string_lossy().to_string());
+		let file_url = Url::from_file_path(path.canonicalize().unwrap_or_else(|_| path.to_path_buf()))
+			.unwrap_or_else(|_| Url::parse("file:///").unwrap());
+
+		let entity = match self.entity(path) {
+			Some(entity) => entity,
+			None => {
+				log::error!("{}", VerifierError::UnknownEntryType(path.to_path_buf()));
+				return vec![EndpointReport::new(&file_name, &file_name, file_url)
+					.null(VerifierError::UnknownEntryType(path.to_path_buf()))];
+			},
+		};
+
+		let records = match Self::read_records(path) {
+			Ok(records) => records,
+			Err(e) => {
+				log::error!("{}", e);
+				return vec![EndpointReport::new(&entity.name, &file_name, file_url).error(e)];
+			},
+		};
+
+		log::info!("Validating {} records of {:?} as {}", records.len(), path, entity.name);
+		records
+			.into_iter()
+			.map(|(location, record)| {
+				let mut record_url = file_url.clone();
+				if !location.is_empty() {
+					record_url.set_fragment(Some(&location));
+				}
+				let record = match record {
+					Ok(record) => record,
+					Err(e) => {
+						log::error!("{}", e);
+						let name = format!("{} record {}", file_name, location);
+						return EndpointReport::new(&entity.name, &name, record_url).error(e);
+					},
+				};
+				let name = format!(
+					"{} record {}",
+					file_name,
+					utils::record_id(&record, &entity.id_pointer).unwrap_or(location)
+				);
+				match utils::valid_schema(&entity.schema, &record) {
+					Ok(_) => EndpointReport::new(&entity.name, &name, record_url).ok(None),
+					Err(e) => EndpointReport::new(&entity.name, &name, record_url).error(e),
+				}
+			})
+			.collect()
+	}
+
+	pub fn validate(self) -> BeaconOutput {
+		let mut output = Output::new();
+
+		for path in &self.files {
+			self.validate_file(path)
+				.into_iter()
+				.for_each(|report| output.push(report));
+		}
+
+		let root = std::env::current_dir()
+			.ok()
+			.and_then(|dir| Url::from_directory_path(dir).ok())
+			.unwrap_or_else(|| Url::parse("file:///").unwrap());
+
+		BeaconOutput {
+			name: String::from("Local data files"),
+			url: root,
+			last_updated: chrono::offset::Utc::now().naive_utc().round_subsecs(6),
+			model: Some(self.model.source),
+			framework: None,
+			spec_version: None,
+			release: None,
+			handovers: BTreeMap::new(),
+			profile: None,
+			profiles: BTreeMap::new(),
+			requirements: Vec::new(),
+			entities: output.finish(),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+
+	use serde_json::json;
+
+	use crate::data::DataFiles;
+
+	/// Records read from a file with the given name and contents, with the messages of the bad ones
+	fn read(name: &str, contents: &str) -> Vec<(String, Result<serde_json::Value, String>)> {
+		let dir = tempfile::tempdir().unwrap();
+		let path = dir.path().join(name);
+		std::fs::write(&path, contents).unwrap();
+		DataFiles::read_records(&path)
+			.unwrap()
+			.into_iter()
+			.map(|(location, record)| {
+				let record = record.map_err(|e| e.to_string().replace(&path.display().to_string(), name));
+				(location, record)
+			})
+			.collect()
+	}
+
+	#[test]
+	fn test_read_json_lines() {
+		assert_eq!(
+			read("individuals.jsonl", "{\"id\": \"ind1\"}\n\n{\"id\": \"ind2\"}\n"),
+			vec![
+				(String::from("L1"), Ok(json!({"id": "ind1"}))),
+				(String::from("L3"), Ok(json!({"id": "ind2"}))),
+			]
+		);
+	}
+
+	#[test]
+	fn test_read_json_lines_bad_line() {
+		assert_eq!(
+			read(
+				"individuals.jsonl",
+				"{\"id\": \"ind1\"}\n{\"id\": \"ind2\",}\n{\"id\": \"ind3\"}\n"
+			),
+			vec![
+				(String::from("L1"), Ok(json!({"id": "ind1"}))),
+				(
+					String::from("L2"),
+					Err(String::from(
+						"Bad data file individuals.jsonl: trailing comma at line 1 column 15"
+					))
+				),
+				(String::from("L3"), Ok(json!({"id": "ind3"}))),
+			]
+		);
+	}
+
+	#[test]
+	fn test_read_json() {
+		assert_eq!(
+			read("individuals.json", r#"[{"id": "ind1"}, {"id": "ind2"}]"#),
+			vec![
+				(String::from("/0"), Ok(json!({"id": "ind1"}))),
+				(String::from("/1"), Ok(json!({"id": "ind2"}))),
+			]
+		);
+		assert_eq!(
+			read("individual.json", r#"{"id": "ind1"}"#),
+			vec![(String::new(), Ok(json!({"id": "ind1"})))]
+		);
+
+		let dir = tempfile::tempdir().unwrap();
+		let path = dir.path().join("individuals.json");
+		std::fs::write(&path, r#"[{"id": "ind1"},"#).unwrap();
+		assert_eq!(
+			DataFiles::read_records(&path).unwrap_err().to_string(),
+			format!(
+				"Bad data file {}: EOF while parsing a value at line 1 column 16",
+				path.display()
+			)
+		);
+	}
+}
@@ -1,72 +1,143 @@
 use std::rc::Rc;
 
 use jsonschema::JSONSchema;
+use reqwest::blocking::Client;
 use url::Url;
 
+use crate::checks::Check;
+use crate::consistency::BeaconIdentity;
 use crate::error::VerifierError;
+use crate::framework::ResponseSchemas;
+use crate::handovers::Handovers;
+use crate::integrity::Integrity;
 use crate::interface::{BeaconMetaGranularityResponse, Granularity};
 use crate::output::EndpointReport;
-use crate::{utils, Json};
+use crate::schemas::BeaconSchemas;
+use crate::{consistency, utils, Json};
 
 pub struct BeaconEndpoint {
 	pub entity_name: String,
+	pub entry_type: String,
 	pub entity_schema: Rc<JSONSchema>,
 	pub name: String,
+	/// Url template (RFC 6570) the url was expanded from
+	pub template: String,
 	pub url: Url,
+	pub integrity: Integrity,
+	pub check: Check,
 }
 
 impl BeaconEndpoint {
+	/// Validates the response of the endpoint. Returns the report of the check of the endpoint, on the schemas of the
+	/// response and of its records, and a report of each check of the content of the response.
 	pub fn validate(
-		self,
+		mut self,
+		client: &Client,
 		root_url: &Url,
-		boolean_json: &Rc<JSONSchema>,
-		count_json: &Rc<JSONSchema>,
-		result_sets_json: &Rc<JSONSchema>,
-		collections_json: &Rc<JSONSchema>,
-	) -> EndpointReport {
+		responses: &ResponseSchemas,
+		schemas: &BeaconSchemas,
+		identity: &BeaconIdentity,
+		handovers: &mut Handovers,
+	) -> Vec<EndpointReport> {
 		let endpoint_url = utils::url_join(root_url, &self.url);
 		log::debug!("GET {}", endpoint_url);
 
+		let (entity_name, name, template) = (self.entity_name.clone(), self.name.clone(), self.template.clone());
+		let report = |check, suffix: Option<&str>| {
+			let name = suffix.map_or_else(|| name.clone(), |suffix| format!("{} {}", name, suffix));
+			EndpointReport::new(&entity_name, &name, endpoint_url.clone())
+				.template(Some(template.clone()))
+				.check(check)
+		};
+		let checked = |check, suffix, result: Result<(), VerifierError>| match result {
+			Ok(()) => report(check, Some(suffix)).ok(None),
+			Err(e) => report(check, Some(suffix)).error(e),
+		};
+
 		// Get response
-		let response_json = match utils::ping_url(&endpoint_url) {
+		let response_json = match utils::ping_url(client, &endpoint_url) {
 			Ok(j) => j,
-			Err(e) => {
-				return EndpointReport::new(&self.entity_name, &self.name, endpoint_url).null(e);
-			},
+			Err(e) => return vec![report(self.check, None).null(e)],
 		};
 
 		// Test granularity
-		let beacon_meta_response: Result<BeaconMetaGranularityResponse, _> =
-			serde_json::from_value(response_json.clone());
+		let br: BeaconMetaGranularityResponse = match serde_json::from_value(response_json.clone()) {
+			Ok(br) => br,
+			Err(e) => return vec![report(self.check, None).error(e.into())],
+		};
 
-		// Test response
-		match beacon_meta_response {
-			Ok(br) => {
-				let valid_against_framework = match br.meta.returned_granularity {
-					Granularity::Boolean => self.validate_against_framework(&response_json, boolean_json),
-					Granularity::Count => self.validate_against_framework(&response_json, count_json),
-					Granularity::Aggregated | Granularity::Record => match self.entity_name.to_lowercase().as_str() {
-						"dataset" | "cohort" => self.validate_against_framework(&response_json, collections_json),
-						_ => self.validate_against_framework(&response_json, result_sets_json),
-					},
-				};
-				if let Err(e) = valid_against_framework {
-					return EndpointReport::new(&self.entity_name, &self.name, endpoint_url).error(e);
-				}
+		// Test the content of the response
+		let mut reports = vec![
+			checked(
+				Check::Consistency,
+				"consistency",
+				consistency::check_response(&response_json),
+			),
+			checked(
+				Check::RequestSummary,
+				"request summary",
+				consistency::check_request_summary(&endpoint_url, &response_json),
+			),
+			checked(Check::Identity, "identity", identity.check(&response_json)),
+		];
+		match handovers.check_response(&self.entity_name, &response_json) {
+			// Nothing to check
+			Ok(0) => (),
+			Ok(_) => reports.push(report(Check::Handovers, Some("handovers")).ok(None)),
+			Err(e) => reports.push(report(Check::Handovers, Some("handovers")).error(e)),
+		}
 
-				if Granularity::Record == br.meta.returned_granularity {
-					// Compile entity schema
-					match self.entity_name.to_lowercase().as_str() {
-						"dataset" | "cohort" => self.validate_collections_response(&response_json).url(endpoint_url),
-						_ => self.validate_resultset_response(&response_json).url(endpoint_url),
-					}
-				}
-				else {
-					EndpointReport::new(&self.entity_name, &self.name, endpoint_url).ok(Some(response_json))
-				}
+		// Test response
+		let response_schema = match br.meta.returned_granularity {
+			Granularity::Boolean => &responses.boolean,
+			Granularity::Count => &responses.count,
+			Granularity::Aggregated | Granularity::Record => match self.entity_name.to_lowercase().as_str() {
+				"dataset" | "cohort" => &responses.collections,
+				_ => &responses.result_sets,
 			},
-			Err(e) => EndpointReport::new(&self.entity_name, &self.name, endpoint_url).error(e.into()),
+		};
+		if let Err(e) = self.validate_against_framework(&response_json, response_schema) {
+			reports.push(report(self.check, None).error(e));
 		}
+		else if Granularity::Record == br.meta.returned_granularity {
+			// Use the schema claimed by the response, if any
+			let schema_name = match schemas.claimed(&self.entry_type, &br.meta.returned_schemas) {
+				Ok(Some(claimed)) => {
+					self.entity_schema = claimed.schema;
+					Some(claimed.name)
+				},
+				Ok(None) => None,
+				Err(e) => {
+					reports.push(report(self.check, None).error(e));
+					return reports;
+				},
+			};
+
+			// Check that the records are the requested ones
+			let integrity = std::mem::take(&mut self.integrity);
+			reports.push(checked(
+				Check::Integrity,
+				"integrity",
+				integrity.check(client, root_url, &utils::records(&response_json)),
+			));
+
+			let check = self.check;
+			let records = match self.entity_name.to_lowercase().as_str() {
+				"dataset" | "cohort" => self.validate_collections_response(&response_json),
+				_ => self.validate_resultset_response(&response_json),
+			};
+			reports.push(
+				records
+					.url(endpoint_url.clone())
+					.template(Some(template.clone()))
+					.check(check)
+					.schema(schema_name),
+			);
+		}
+		else {
+			reports.push(report(self.check, None).ok(Some(response_json)));
+		}
+		reports
 	}
 
 	pub fn validate_against_framework(
@@ -74,9 +145,7 @@ impl BeaconEndpoint {
 		response_json: &Json,
 		response_schema: &Rc<JSONSchema>,
 	) -> Result<(), VerifierError> {
-		if let Err(e) = utils::valid_schema(response_schema, response_json) {
-			return Err(e);
-		};
+		utils::valid_schema(response_schema, response_json)?;
 		Ok(())
 	}
 
@@ -111,12 +180,14 @@ impl BeaconEndpoint {
 			.as_array()
 			.expect("'collections' property is not an array")
 			.iter()
-			.map(
-				|instance| match utils::valid_schema(&self.entity_schema.clone(), &instance.clone()) {
+			.enumerate()
+			.map(|(i, instance)| {
+				let pointer = format!("/response/collections/{}", i);
+				match utils::valid_schema_at(&self.entity_schema.clone(), &instance.clone(), &pointer) {
 					Ok(output) => EndpointReport::new(&self.entity_name, &self.name, self.url.clone()).ok(Some(output)),
 					Err(e) => EndpointReport::new(&self.entity_name, &self.name, self.url.clone()).error(e),
-				},
-			)
+				}
+			})
 			.fold(
 				EndpointReport::new(&self.entity_name, &self.name, self.url.clone()).ok(None),
 				EndpointReport::join,
@@ -154,7 +225,8 @@ impl BeaconEndpoint {
 			.as_array()
 			.expect("'resultSets' property is not an array")
 			.iter()
-			.map(|rs| {
+			.enumerate()
+			.map(|(i, rs)| {
 				rs.as_object()
 					.expect("resultSet inside 'resultSets' property is not an object")
 					.get("results")
@@ -162,14 +234,16 @@ impl BeaconEndpoint {
 					.as_array()
 					.expect("'results' property is not an array")
 					.iter()
-					.map(
-						|instance| match utils::valid_schema(&self.entity_schema.clone(), &instance.clone()) {
+					.enumerate()
+					.map(|(j, instance)| {
+						let pointer = format!("/response/resultSets/{}/results/{}", i, j);
+						match utils::valid_schema_at(&self.entity_schema.clone(), &instance.clone(), &pointer) {
 							Ok(output) => {
 								EndpointReport::new(&self.entity_name, &self.name, self.url.clone()).ok(Some(output))
 							},
 							Err(e) => EndpointReport::new(&self.entity_name, &self.name, self.url.clone()).error(e),
-						},
-					)
+						}
+					})
 					.fold(
 						EndpointReport::new(&self.entity_name, &self.name, self.url.clone()).ok(None),
 						EndpointReport::join,
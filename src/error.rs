@@ -2,6 +2,8 @@ use std::path::PathBuf;
 
 use thiserror::Error;
 
+use crate::checks::Severity;
+
 #[derive(Error, Debug)]
 pub enum VerifierError {
 	#[error("Request error {0}")]
@@ -31,12 +33,111 @@ pub enum VerifierError {
 	#[error("Response does not match the schema: {0}")]
 	BadResponse(String),
 
+	#[error("Response has properties that are not in the schema: {0}")]
+	UnexpectedProperties(String),
+
 	#[error("Unexpected HTTP status code")]
 	BadStatus,
 
-	// TODO: Use errors
-	// #[error("No ids were extracted from the main entity endpoint")]
-	// NoIds,
+	#[error("Git error: {0}")]
+	GitError(#[from] git2::Error),
+
+	#[error("Unknown reference {0} in {1}")]
+	UnknownReference(String, String),
+
+	#[error("Repository {0} is not cached (offline mode)")]
+	NotCached(String),
+
+	#[error("Not available in offline mode")]
+	Offline,
+
+	#[error("Bad archive {0}: {1}")]
+	BadArchive(url::Url, String),
+
+	#[error("No snapshot {0} was embedded in this build")]
+	NoSnapshot(url::Url),
+
+	#[error("{0} is not a local path")]
+	NotLocalPath(url::Url),
+
+	#[error("Unable to resolve $ref {1} ({0}) with the files of the framework and the model")]
+	UnresolvedReference(url::Url, String),
+
+	#[error("The response claims schema {0} for {1}, which is not advertised in /entry_types or /configuration")]
+	UnadvertisedSchema(String, String),
+
+	#[error("Schema {0} advertised by the beacon could not be loaded: {1}")]
+	UnavailableSchema(String, String),
+
+	#[error("Entry type {0} of the beacon map is not advertised in /entry_types or /configuration")]
+	UnadvertisedEntryType(String),
+
+	#[error("Referential integrity is broken: {0}")]
+	BrokenReference(String),
+
+	#[error("Inconsistent response: {0}")]
+	InconsistentResponse(String),
+
+	#[error("Bad handovers: {0}")]
+	BadHandover(String),
+
+	#[error("/service-info does not match /info: {0}")]
+	ServiceInfoMismatch(String),
+
+	#[error("The allele request was answered with an error: {0}")]
+	QueryError(String),
+
+	#[error("/ has no sampleAlleleRequests to query the beacon with")]
+	NoSampleAlleleRequest,
+
+	#[error("Bad releases file {0}: {1}")]
+	BadReleases(PathBuf, String),
+
+	#[error("Bad waivers file {0}: {1}")]
+	BadWaivers(PathBuf, String),
+
+	#[error("Bad test suite {0}: {1}")]
+	BadSuite(PathBuf, String),
+
+	#[error("The answer is not the expected one: {0}")]
+	UnexpectedAnswer(String),
+
+	#[error("The response does not identify the beacon like /info: {0}")]
+	IdentityMismatch(String),
+
+	#[error("apiVersion {0} is not a semantic version: {1}")]
+	BadApiVersion(String, String),
+
+	#[error("meta.receivedRequestSummary does not match the request: {0}")]
+	RequestSummaryMismatch(String),
+
+	#[error("Bad request not rejected properly: {0}")]
+	BadErrorResponse(String),
+
+	#[error("Bad data file {0}: {1}")]
+	BadDataFile(PathBuf, String),
+
+	#[error("No entry type of the model matches the data file: {0}")]
+	UnknownEntryType(PathBuf),
+
+	#[error("No ids were extracted from the main entity endpoint with {0} (use the --id-field option)")]
+	NoIds(String),
+
 	#[error("Error deserializing JSON: {0}")]
 	SerdeJsonError(#[from] serde_json::Error),
 }
+
+impl VerifierError {
+	/// Severity of the error regardless of the check it comes from, if any
+	pub fn severity(&self) -> Option<Severity> {
+		match self {
+			// Extensions of the schemas are usually deliberate
+			Self::UnexpectedProperties(_) => Some(Severity::Warning),
+			// Nothing was tested
+			Self::NoSampleAlleleRequest => Some(Severity::Info),
+			// The ids may just be elsewhere (see --id-field)
+			Self::NoIds(_) => Some(Severity::Warning),
+			_ => None,
+		}
+	}
+}
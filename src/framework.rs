@@ -2,11 +2,13 @@ use std::collections::BTreeMap;
 use std::ffi::OsStr;
 use std::fs::File;
 use std::path::{Path, PathBuf};
+use std::rc::Rc;
 
-use git2::Repository;
-use url::Url;
+use jsonschema::JSONSchema;
 
 use crate::error::VerifierError;
+use crate::resolver::LocalResolver;
+use crate::source::Source;
 use crate::{utils, Json};
 
 #[derive(Debug, Clone)]
@@ -19,40 +21,26 @@ pub struct Framework {
 	pub boolean_json: Json,
 	pub count_json: Json,
 	pub collections_json: Json,
+	pub error_json: Json,
+	pub configuration_schema_json: Json,
+	pub beacon_map_schema_json: Json,
+	pub endpoints_schema_json: Json,
 	files: BTreeMap<PathBuf, Json>,
+	pub source: Source,
+	pub resolver: LocalResolver,
 }
 
-impl Framework {
-	pub fn load(location: &Url) -> Result<Self, VerifierError> {
-		let dir = tempfile::tempdir().expect("Could not create temporary directory");
-
-		if location.scheme() == "file" {
-			log::debug!("COPYING {} to {:?}", location.path(), dir.path());
-			utils::copy_dir_recursively(location.path(), &dir).expect("Copy dir recursively failed");
-		}
-		else {
-			// Parse model repo URL
-			assert_eq!(
-				location.domain().unwrap_or(""),
-				"github.com",
-				"Only repos hosted on github.com are supported"
-			);
-			let mut url_iter = Path::new(location.path()).components().skip(1);
-			let owner = url_iter.next().unwrap().as_os_str().to_string_lossy().to_string();
-			let repo = url_iter.next().unwrap().as_os_str().to_string_lossy().to_string();
-			let path: PathBuf = url_iter.collect();
-
-			log::debug!("Downloading repo {} from {}", repo, owner);
-			log::debug!("Path inside repo = {:?}", path);
-
-			// Clone repo to tempdir
-			let repo_url = format!("https://github.com/{owner}/{repo}", owner = owner, repo = repo);
-			let full_git_dir = tempfile::tempdir().expect("Could not create temporary directory");
-			Repository::clone(&repo_url, full_git_dir.path()).expect("Unable to clone repository");
+/// Compiled schemas of the responses of the entry type endpoints
+pub struct ResponseSchemas {
+	pub boolean: Rc<JSONSchema>,
+	pub count: Rc<JSONSchema>,
+	pub result_sets: Rc<JSONSchema>,
+	pub collections: Rc<JSONSchema>,
+}
 
-			// Copy subfolder to the final tempdir
-			utils::copy_dir_recursively(full_git_dir.path().join(path), dir.path()).unwrap();
-		}
+impl Framework {
+	pub fn load(mut source: Source) -> Result<Self, VerifierError> {
+		let dir = source.fetch()?;
 
 		let mut framework = Self {
 			info_json: Json::Null,
@@ -63,7 +51,13 @@ impl Framework {
 			boolean_json: Json::Null,
 			count_json: Json::Null,
 			collections_json: Json::Null,
+			error_json: Json::Null,
+			configuration_schema_json: Json::Null,
+			beacon_map_schema_json: Json::Null,
+			endpoints_schema_json: Json::Null,
 			files: BTreeMap::new(),
+			source,
+			resolver: LocalResolver::default(),
 		};
 
 		// Load files
@@ -73,12 +67,25 @@ impl Framework {
 			}
 		}
 
+		framework
+			.resolver
+			.add_files("framework", &framework.source.location, dir.path(), &framework.files);
+
 		// Load configuration
 		framework.load_configuration(dir.path());
 
 		Ok(framework)
 	}
 
+	pub fn response_schemas(&self) -> ResponseSchemas {
+		ResponseSchemas {
+			boolean: utils::compile_schema(&self.boolean_json, &self.resolver),
+			count: utils::compile_schema(&self.count_json, &self.resolver),
+			result_sets: utils::compile_schema(&self.result_sets_json, &self.resolver),
+			collections: utils::compile_schema(&self.collections_json, &self.resolver),
+		}
+	}
+
 	fn add(&mut self, path: &Path) -> Result<(), VerifierError> {
 		log::debug!("Adding JSON file: {:?}", path);
 		let file = File::open(path).unwrap();
@@ -87,46 +94,54 @@ impl Framework {
 		Ok(())
 	}
 
+	/// Schema of the framework with its base url, to resolve its relative `$ref`s
+	fn schema(&self, base_path: &Path, rel_path: &Path) -> Option<Json> {
+		self.files
+			.get(&base_path.join(rel_path))
+			.map(|schema| LocalResolver::with_base(schema, &LocalResolver::base_url("framework", rel_path)))
+	}
+
 	fn load_configuration(&mut self, base_path: &Path) {
+		let responses_dir = Path::new("responses");
 		self.info_json = self
-			.files
-			.get(&base_path.join("responses").join("beaconInfoResponse.json"))
-			.expect("beaconInfoResponse.json not found")
-			.clone();
+			.schema(base_path, &responses_dir.join("beaconInfoResponse.json"))
+			.expect("beaconInfoResponse.json not found");
 		self.beacon_map_json = self
-			.files
-			.get(&base_path.join("responses").join("beaconMapResponse.json"))
-			.expect("beaconMapResponse.json not found")
-			.clone();
+			.schema(base_path, &responses_dir.join("beaconMapResponse.json"))
+			.expect("beaconMapResponse.json not found");
 		self.configuration_json = self
-			.files
-			.get(&base_path.join("responses").join("beaconConfigurationResponse.json"))
-			.expect("beaconConfigurationResponse.json not found")
-			.clone();
+			.schema(base_path, &responses_dir.join("beaconConfigurationResponse.json"))
+			.expect("beaconConfigurationResponse.json not found");
 		self.entry_types_json = self
-			.files
-			.get(&base_path.join("responses").join("beaconEntryTypesResponse.json"))
-			.expect("beaconEntryTypesResponse.json not found")
-			.clone();
+			.schema(base_path, &responses_dir.join("beaconEntryTypesResponse.json"))
+			.expect("beaconEntryTypesResponse.json not found");
 		self.boolean_json = self
-			.files
-			.get(&base_path.join("responses").join("beaconBooleanResponse.json"))
-			.expect("beaconBooleanResponse.json not found")
-			.clone();
+			.schema(base_path, &responses_dir.join("beaconBooleanResponse.json"))
+			.expect("beaconBooleanResponse.json not found");
 		self.count_json = self
-			.files
-			.get(&base_path.join("responses").join("beaconCountResponse.json"))
-			.expect("beaconCountResponse.json not found")
-			.clone();
+			.schema(base_path, &responses_dir.join("beaconCountResponse.json"))
+			.expect("beaconCountResponse.json not found");
 		self.result_sets_json = self
-			.files
-			.get(&base_path.join("responses").join("beaconResultsetsResponse.json"))
-			.expect("beaconResultsetsResponse.json not found")
-			.clone();
+			.schema(base_path, &responses_dir.join("beaconResultsetsResponse.json"))
+			.expect("beaconResultsetsResponse.json not found");
 		self.collections_json = self
-			.files
-			.get(&base_path.join("responses").join("beaconCollectionsResponse.json"))
-			.expect("beaconCollectionsResponse.json not found")
-			.clone();
+			.schema(base_path, &responses_dir.join("beaconCollectionsResponse.json"))
+			.expect("beaconCollectionsResponse.json not found");
+		// Older frameworks may not define the error response
+		self.error_json = self
+			.schema(base_path, &responses_dir.join("beaconErrorResponse.json"))
+			.unwrap_or(Json::Null);
+
+		// Configuration schemas are only needed to lint models
+		let configuration_dir = Path::new("configuration");
+		self.configuration_schema_json = self
+			.schema(base_path, &configuration_dir.join("beaconConfigurationSchema.json"))
+			.unwrap_or(Json::Null);
+		self.beacon_map_schema_json = self
+			.schema(base_path, &configuration_dir.join("beaconMapSchema.json"))
+			.unwrap_or(Json::Null);
+		self.endpoints_schema_json = self
+			.schema(base_path, &configuration_dir.join("endpointsSchema.json"))
+			.unwrap_or(Json::Null);
 	}
 }
@@ -0,0 +1,167 @@
+use std::collections::{BTreeMap, HashMap};
+
+use reqwest::blocking::Client;
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+use crate::error::VerifierError;
+use crate::{utils, Json};
+
+/// Handovers found in the responses of an entity
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct HandoverCoverage {
+	/// Responses checked
+	pub responses: usize,
+	/// Responses with at least one handover
+	pub with_handovers: usize,
+	pub handovers: usize,
+	/// Handovers that are not valid or do not respond
+	pub broken: usize,
+	/// Handovers to external hosts that were not requested
+	pub skipped: usize,
+}
+
+/// Checks the handovers (`beaconHandovers`, `resultsHandovers`...) of the responses of a beacon
+pub struct Handovers {
+	client: Client,
+	host: Option<String>,
+	skip_external: bool,
+	/// Reachability of the urls already requested
+	reachable: HashMap<Url, Result<(), String>>,
+	coverage: BTreeMap<String, HandoverCoverage>,
+}
+
+impl Handovers {
+	/// With `skip_external`, the urls outside the host of the beacon are not requested
+	pub fn new(client: &Client, beacon_url: &Url, skip_external: bool) -> Self {
+		Self {
+			client: client.clone(),
+			host: beacon_url.host_str().map(String::from),
+			skip_external,
+			reachable: HashMap::new(),
+			coverage: BTreeMap::new(),
+		}
+	}
+
+	/// Checks that the handovers of a response have an ontology term as `handoverType` and a url that responds.
+	/// Returns the number of handovers.
+	pub fn check_response(&mut self, entity_name: &str, response: &Json) -> Result<usize, VerifierError> {
+		let mut handovers = Vec::new();
+		collect(response, &mut handovers);
+
+		let mut errors = Vec::new();
+		let mut skipped = 0;
+		for handover in &handovers {
+			match self.check(handover) {
+				Ok(true) => (),
+				Ok(false) => skipped += 1,
+				Err(e) => errors.push(e),
+			}
+		}
+
+		let coverage = self.coverage.entry(entity_name.to_string()).or_default();
+		coverage.responses += 1;
+		coverage.with_handovers += usize::from(!handovers.is_empty());
+		coverage.handovers += handovers.len();
+		coverage.broken += errors.len();
+		coverage.skipped += skipped;
+
+		if errors.is_empty() {
+			Ok(handovers.len())
+		}
+		else {
+			Err(VerifierError::BadHandover(errors.join("\n")))
+		}
+	}
+
+	/// Whether the handover was requested (it is not if it is external and they are skipped)
+	fn check(&mut self, handover: &Json) -> Result<bool, String> {
+		let handover_type = &handover["handoverType"];
+		match handover_type["id"].as_str() {
+			Some(id) if is_curie(id) => (),
+			_ => return Err(format!("handoverType {} is not an ontology term", handover_type)),
+		}
+		if !matches!(handover_type.get("label"), None | Some(Json::String(_))) {
+			return Err(format!(
+				"handoverType {} has a label that is not a string",
+				handover_type
+			));
+		}
+
+		let url = handover["url"]
+			.as_str()
+			.and_then(|url| Url::parse(url).ok())
+			.filter(|url| matches!(url.scheme(), "http" | "https" | "ftp"))
+			.ok_or_else(|| format!("{} is not a valid handover url", handover["url"]))?;
+		if self.skip_external && url.host_str().map(String::from) != self.host {
+			log::debug!("Skipping external handover {}", url);
+			return Ok(false);
+		}
+		self.reachable
+			.entry(url.clone())
+			.or_insert_with(|| {
+				log::debug!("Requesting handover {}", url);
+				match utils::url_status(&self.client, &url) {
+					Ok(status) if status < 400 => Ok(()),
+					Ok(status) => Err(format!("Handover {} answered with HTTP {}", url, status)),
+					Err(e) => Err(format!("Handover {} did not respond: {}", url, e)),
+				}
+			})
+			.clone()
+			.map(|()| true)
+	}
+
+	pub fn coverage(self) -> BTreeMap<String, HandoverCoverage> {
+		self.coverage
+	}
+}
+
+/// Handovers in the arrays whose key ends in `Handovers`, at any depth
+fn collect<'a>(json: &'a Json, handovers: &mut Vec<&'a Json>) {
+	match json {
+		Json::Object(object) => {
+			for (key, value) in object {
+				match value.as_array() {
+					Some(array) if key.ends_with("Handovers") => handovers.extend(array),
+					_ => collect(value, handovers),
+				}
+			}
+		},
+		Json::Array(array) => array.iter().for_each(|value| collect(value, handovers)),
+		_ => (),
+	}
+}
+
+/// Compact URI of an ontology term (e.g. `EFO:0004157`)
+fn is_curie(id: &str) -> bool {
+	id.split_once(':').map_or(false, |(prefix, reference)| {
+		!prefix.is_empty() && !reference.is_empty() && !id.contains(char::is_whitespace)
+	})
+}
+
+#[cfg(test)]
+mod tests {
+
+	use serde_json::json;
+
+	use crate::handovers::{collect, is_curie};
+
+	#[test]
+	fn test_collect() {
+		let response = json!({
+			"beaconHandovers": [{"handoverType": {"id": "EDAM:operation_0533"}, "url": "https://example.org/a"}],
+			"response": {"resultSets": [
+				{"resultsHandovers": [{"handoverType": {"id": "CUSTOM:igv"}, "url": "https://example.org/b"}]},
+				{"results": []}
+			]}
+		});
+		let mut handovers = Vec::new();
+		collect(&response, &mut handovers);
+		assert_eq!(handovers.len(), 2);
+
+		assert!(is_curie("EDAM:operation_0533"));
+		assert!(!is_curie("igv"));
+		assert!(!is_curie("EDAM:"));
+		assert!(!is_curie("EDAM: 0533"));
+	}
+}
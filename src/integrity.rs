@@ -0,0 +1,184 @@
+use reqwest::blocking::Client;
+use url::Url;
+
+use crate::error::VerifierError;
+use crate::sampling::Sampling;
+use crate::{utils, Json};
+
+/// Reference from the records of an entity to the records of another one (e.g. `individualId` of biosamples)
+#[derive(Debug, Clone)]
+pub struct Reference {
+	pub pointer: String,
+	pub entity_name: String,
+	/// Single entry url template of the referenced entity
+	pub template: String,
+	pub id_pointer: String,
+}
+
+/// Checks that the records returned by an endpoint are the ones that were requested
+#[derive(Debug, Clone, Default)]
+pub struct Integrity {
+	/// JSON pointer to the id of the records and requested id, all the records must have it
+	pub single: Option<(String, String)>,
+	/// JSON pointer to the reference to the parent and id of the parent, all the records must reference it
+	pub parent: Option<(String, String)>,
+	/// References to other entities, they must resolve with their single entry endpoint
+	pub references: Vec<Reference>,
+	/// Ids of each reference that are resolved, one request each
+	pub sampling: Sampling,
+}
+
+impl Integrity {
+	pub fn check(&self, client: &Client, root_url: &Url, records: &[&Json]) -> Result<(), VerifierError> {
+		let mut errors = Vec::new();
+
+		if let Some((id_pointer, id)) = &self.single {
+			let ids = records
+				.iter()
+				.filter_map(|record| utils::record_id(record, id_pointer))
+				.collect::<Vec<_>>();
+			if records.len() != 1 || ids != [id.clone()] {
+				errors.push(format!("{} was requested but the records returned are {:?}", id, ids));
+			}
+		}
+
+		if let Some((pointer, id)) = &self.parent {
+			for (i, record) in records.iter().enumerate() {
+				match utils::record_id(record, pointer) {
+					Some(parent) if &parent != id => {
+						errors.push(format!("Record {} has {} {} instead of {}", i, pointer, parent, id));
+					},
+					Some(_) => (),
+					None => errors.push(format!("Record {} has no {} (it should be {})", i, pointer, id)),
+				}
+			}
+		}
+
+		for reference in &self.references {
+			let mut ids = Vec::new();
+			for id in records
+				.iter()
+				.filter_map(|record| utils::record_id(record, &reference.pointer))
+			{
+				if !ids.contains(&id) {
+					ids.push(id);
+				}
+			}
+			let sampled = self.sampling.sample(&ids);
+			if sampled.len() < ids.len() {
+				log::debug!(
+					"Resolving {} of the {} {} referenced",
+					sampled.len(),
+					ids.len(),
+					reference.entity_name
+				);
+			}
+			for id in sampled {
+				if let Err(message) = reference.resolve(client, root_url, id) {
+					errors.push(message);
+				}
+			}
+		}
+
+		if errors.is_empty() {
+			Ok(())
+		}
+		else {
+			Err(VerifierError::BrokenReference(errors.join("\n")))
+		}
+	}
+}
+
+impl Reference {
+	fn resolve(&self, client: &Client, root_url: &Url, id: &str) -> Result<(), String> {
+		let url = utils::expand_template(&self.template, &[("id", id)])
+			.and_then(|url| Url::parse(&url).ok())
+			.ok_or_else(|| format!("Bad url template {}", self.template))?;
+		let url = utils::url_join(root_url, &url);
+		log::debug!("Resolving {} {} ({})", self.entity_name, id, url);
+
+		let response = utils::ping_url(client, &url).map_err(|e| {
+			format!(
+				"{} {} ({}) could not be resolved: {}",
+				self.entity_name, id, self.pointer, e
+			)
+		})?;
+		if utils::records(&response)
+			.iter()
+			.any(|record| utils::record_id(record, &self.id_pointer).as_deref() == Some(id))
+		{
+			Ok(())
+		}
+		else {
+			Err(format!(
+				"{} {} ({}) was not found in {}",
+				self.entity_name, id, self.pointer, url
+			))
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+
+	use serde_json::json;
+	use url::Url;
+
+	use crate::integrity::{Integrity, Reference};
+	use crate::sampling::Sampling;
+	use crate::utils;
+
+	#[test]
+	fn test_check_parent() {
+		let integrity = Integrity {
+			parent: Some((String::from("/individualId"), String::from("ind1"))),
+			..Integrity::default()
+		};
+		let client = utils::client(false).unwrap();
+		let root_url = Url::parse("http://127.0.0.1:1/api").unwrap();
+		let records = [
+			json!({"id": "bio1", "individualId": "ind1"}),
+			json!({"id": "bio2", "individualId": "ind2"}),
+			json!({"id": "bio3"}),
+		];
+		let records = records.iter().collect::<Vec<_>>();
+
+		assert!(integrity.check(&client, &root_url, &records[..1]).is_ok());
+		assert_eq!(
+			integrity.check(&client, &root_url, &records).unwrap_err().to_string(),
+			"Referential integrity is broken: Record 1 has /individualId ind2 instead of ind1\nRecord 2 has no \
+			 /individualId (it should be ind1)"
+		);
+	}
+
+	#[test]
+	fn test_check_dangling_reference() {
+		let integrity = Integrity {
+			references: vec![Reference {
+				pointer: String::from("/individualId"),
+				entity_name: String::from("Individuals"),
+				template: String::from("http://127.0.0.1:1/api/individuals/{id}"),
+				id_pointer: String::from("/id"),
+			}],
+			sampling: Sampling::First(1),
+			..Integrity::default()
+		};
+		let client = utils::client(false).unwrap();
+		let root_url = Url::parse("http://127.0.0.1:1/api").unwrap();
+		let records = [
+			json!({"id": "bio1", "individualId": "ind1"}),
+			json!({"id": "bio2", "individualId": "ind2"}),
+		];
+		let records = records.iter().collect::<Vec<_>>();
+
+		// Only the first reference is requested
+		let error = integrity.check(&client, &root_url, &records).unwrap_err().to_string();
+		assert_eq!(error.lines().count(), 1);
+		assert!(
+			error
+				.starts_with("Referential integrity is broken: Individuals ind1 (/individualId) could not be resolved"),
+			"{}",
+			error
+		);
+	}
+}
@@ -9,9 +9,10 @@ use url::Url;
 pub struct Endpoint {
 	pub entry_type: String,
 	pub open_apiendpoints_definition: Option<PathBuf>,
-	pub root_url: Url,
-	pub single_entry_url: Option<Url>,
-	pub filtering_terms_url: Option<Url>,
+	/// Url templates (RFC 6570)
+	pub root_url: String,
+	pub single_entry_url: Option<String>,
+	pub filtering_terms_url: Option<String>,
 	pub endpoints: Option<BTreeMap<String, RelatedEndpoint>>,
 }
 
@@ -19,7 +20,7 @@ pub struct Endpoint {
 #[serde(rename_all = "camelCase")]
 pub struct RelatedEndpoint {
 	pub returned_entry_type: String,
-	pub url: Url,
+	pub url: String,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -45,6 +46,27 @@ pub struct DefaultSchema {
 	pub reference_to_schema_definition: String,
 }
 
+/// Schema advertised by a beacon for one of its entry types
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReferenceToAnSchema {
+	pub id: String,
+	pub reference_to_schema_definition: String,
+	pub schema_version: Option<String>,
+}
+
+/// Entry type as advertised by a beacon in `/entry_types` and `/configuration`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BeaconEntryType {
+	pub id: String,
+	pub name: Option<String>,
+	pub default_schema: ReferenceToAnSchema,
+	#[serde(default)]
+	pub additionally_supported_schemas: Vec<ReferenceToAnSchema>,
+}
+
+#[allow(dead_code)] // TODO: Filtering terms
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub enum FilteringTermType {
@@ -53,6 +75,7 @@ pub enum FilteringTermType {
 	Custom,
 }
 
+#[allow(dead_code)] // TODO: Filtering terms
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct FilteringTerm {
@@ -72,41 +95,6 @@ pub enum Granularity {
 	Record,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct BeaconResultSetResponse {
-	pub response: ResultSetResponse,
-}
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct ResultSetResponse {
-	pub result_sets: Vec<ResultSet>,
-}
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct ResultSet {
-	pub results: Vec<EntityResult>,
-}
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-#[serde(untagged)]
-#[serde(rename_all = "camelCase")]
-pub enum EntityResult {
-	General { id: String },
-	Variant { variant_internal_id: String },
-	Cohort { cohort_id: String },
-}
-
-impl EntityResult {
-	pub fn id(&self) -> String {
-		match self {
-			EntityResult::General { id } => id.clone(),
-			EntityResult::Variant { variant_internal_id } => variant_internal_id.clone(),
-			EntityResult::Cohort { cohort_id } => cohort_id.clone(),
-		}
-	}
-}
-
 /// Extract granularity
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -118,4 +106,13 @@ pub struct BeaconMetaGranularityResponse {
 #[serde(rename_all = "camelCase")]
 pub struct MetaGranularityResponse {
 	pub returned_granularity: Granularity,
+	#[serde(default)]
+	pub returned_schemas: Vec<ReturnedSchema>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReturnedSchema {
+	pub entity_type: String,
+	pub schema: String,
 }
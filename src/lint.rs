@@ -0,0 +1,567 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+
+use chrono::SubsecRound;
+use tempfile::TempDir;
+use url::Url;
+
+use crate::error::VerifierError;
+use crate::framework::Framework;
+use crate::model::Model;
+use crate::output::{LintFinding, LintOutput};
+use crate::resolver::LocalResolver;
+use crate::source::Source;
+use crate::{utils, Json};
+
+const BEACON_MAP: &str = "beaconMap.json";
+const CONFIGURATION: &str = "beaconConfiguration.json";
+const ENDPOINTS: &str = "endpoints.json";
+
+/// Checks the consistency of a model without compiling it (as `Model::load` does)
+pub struct Linter {
+	source: Source,
+	dir: TempDir,
+	files: BTreeMap<PathBuf, Json>,
+	resolver: LocalResolver,
+	findings: Vec<LintFinding>,
+}
+
+impl Linter {
+	pub fn load(mut source: Source) -> Result<Self, VerifierError> {
+		let dir = source.fetch()?;
+
+		let mut linter = Self {
+			source,
+			dir,
+			files: BTreeMap::new(),
+			resolver: LocalResolver::default(),
+			findings: Vec::new(),
+		};
+
+		// Load files
+		for entry in walkdir::WalkDir::new(linter.dir.path()).into_iter().flatten() {
+			if entry.path().extension() == Some(OsStr::new("json")) {
+				let rel_path = entry.path().strip_prefix(linter.dir.path()).unwrap().to_path_buf();
+				let contents = std::fs::read_to_string(entry.path())?;
+				match serde_json::from_str(&contents) {
+					Ok(json) => {
+						linter.files.insert(rel_path, json);
+					},
+					Err(e) => linter.push(&rel_path, "", format!("Bad json: {}", e)),
+				}
+			}
+		}
+
+		Ok(linter)
+	}
+
+	pub fn lint(mut self, framework: &Framework) -> LintOutput {
+		self.resolver = framework.resolver.clone();
+		self.resolver
+			.add_files("model", &self.source.location, Path::new(""), &self.files);
+
+		log::info!("Linting \"{}\"", CONFIGURATION);
+		self.check_against_schema(CONFIGURATION, &framework.configuration_schema_json);
+		log::info!("Linting \"{}\"", BEACON_MAP);
+		self.check_against_schema(BEACON_MAP, &framework.beacon_map_schema_json);
+		log::info!("Linting \"{}\"", ENDPOINTS);
+		self.check_against_schema(ENDPOINTS, &framework.endpoints_schema_json);
+
+		log::info!("Linting entry types");
+		let entry_types = self.check_entry_types();
+		log::info!("Linting endpoint sets");
+		self.check_endpoint_sets(&entry_types);
+		log::info!("Linting references");
+		self.check_refs();
+
+		LintOutput {
+			model: self.source,
+			last_updated: chrono::offset::Utc::now().naive_utc().round_subsecs(6),
+			findings: self.findings,
+		}
+	}
+
+	fn push(&mut self, file: &Path, pointer: &str, message: String) {
+		log::error!("{:?}#{}: {}", file, pointer, message);
+		self.findings.push(LintFinding {
+			file: file.to_path_buf(),
+			pointer: pointer.to_string(),
+			message,
+		});
+	}
+
+	fn check_against_schema(&mut self, file_name: &str, schema: &Json) {
+		let file = PathBuf::from(file_name);
+		let instance = match self.files.get(&file) {
+			Some(instance) => instance,
+			None => return self.push(&file, "", format!("{} not found", file_name)),
+		};
+
+		if schema.is_null() {
+			log::warn!("The framework has no schema for {}, skipping", file_name);
+			return;
+		}
+
+		let errors = match jsonschema::JSONSchema::options()
+			.with_meta_schemas()
+			.with_resolver(self.resolver.clone())
+			.compile(schema)
+		{
+			Ok(json_schema) => json_schema.validate(instance).map_or_else(
+				|errors| {
+					errors
+						.map(|e| (e.instance_path.to_string(), e.to_string()))
+						.collect::<Vec<_>>()
+				},
+				|_| Vec::new(),
+			),
+			Err(e) => vec![(
+				String::new(),
+				format!("Unable to compile the framework schema for {}: {}", file_name, e),
+			)],
+		};
+
+		for (pointer, message) in errors {
+			self.push(&file, &pointer, message);
+		}
+	}
+
+	/// Returns the declared entry types
+	fn check_entry_types(&mut self) -> BTreeSet<String> {
+		let file = PathBuf::from(CONFIGURATION);
+		let entry_types = self
+			.files
+			.get(&file)
+			.and_then(|configuration| configuration["entryTypes"].as_object())
+			.cloned()
+			.unwrap_or_default();
+
+		let mut declared = BTreeSet::new();
+		for (key, entry_type) in entry_types {
+			let pointer = format!("/entryTypes/{}", utils::pointer_token(&key));
+			declared.insert(entry_type["id"].as_str().unwrap_or(&key).to_string());
+
+			let reference = match entry_type["defaultSchema"]["referenceToSchemaDefinition"].as_str() {
+				Some(reference) => reference,
+				None => {
+					self.push(&file, &pointer, format!("Entry type {} has no default schema", key));
+					continue;
+				},
+			};
+
+			let pointer = format!("{}/defaultSchema/referenceToSchemaDefinition", pointer);
+			let schema_path = utils::normalize_path(&Model::schema_path(Path::new(""), reference));
+			let message = match self.files.get(&schema_path) {
+				Some(schema) => match jsonschema::JSONSchema::options()
+					.with_meta_schemas()
+					.with_resolver(self.resolver.clone())
+					.compile(&LocalResolver::with_base(
+						schema,
+						&LocalResolver::base_url("model", &schema_path),
+					)) {
+					Ok(_) => continue,
+					Err(e) => format!(
+						"Schema {} of entry type {} could not be compiled: {}",
+						reference, key, e
+					),
+				},
+				None => format!("Schema {} of entry type {} not found", reference, key),
+			};
+			self.push(&file, &pointer, message);
+		}
+
+		declared
+	}
+
+	fn check_endpoint_sets(&mut self, entry_types: &BTreeSet<String>) {
+		let file = PathBuf::from(BEACON_MAP);
+		let endpoint_sets = self
+			.files
+			.get(&file)
+			.and_then(|beacon_map| beacon_map["endpointSets"].as_object())
+			.cloned()
+			.unwrap_or_default();
+
+		for (key, endpoint_set) in endpoint_sets {
+			let pointer = format!("/endpointSets/{}", utils::pointer_token(&key));
+
+			match endpoint_set["entryType"].as_str() {
+				Some(entry_type) if !entry_types.contains(entry_type) => self.push(
+					&file,
+					&format!("{}/entryType", pointer),
+					format!(
+						"Entry type {} is not declared in {} (it has no schema)",
+						entry_type, CONFIGURATION
+					),
+				),
+				_ => (),
+			}
+
+			for url_key in ["rootUrl", "singleEntryUrl", "filteringTermsUrl"] {
+				if let Some(template) = endpoint_set[url_key].as_str() {
+					self.check_url_template(&file, &format!("{}/{}", pointer, url_key), template);
+				}
+			}
+
+			for (related_key, related) in endpoint_set["endpoints"].as_object().cloned().unwrap_or_default() {
+				let pointer = format!("{}/endpoints/{}", pointer, utils::pointer_token(&related_key));
+				match related["returnedEntryType"].as_str() {
+					Some(entry_type) if !entry_types.contains(entry_type) => self.push(
+						&file,
+						&format!("{}/returnedEntryType", pointer),
+						format!(
+							"Related endpoint returns entry type {} which is not declared in {}",
+							entry_type, CONFIGURATION
+						),
+					),
+					_ => (),
+				}
+				if let Some(template) = related["url"].as_str() {
+					self.check_url_template(&file, &format!("{}/url", pointer), template);
+				}
+			}
+		}
+	}
+
+	fn check_url_template(&mut self, file: &Path, pointer: &str, template: &str) {
+		match utils::template_variables(template) {
+			Some(variables) => {
+				for variable in variables {
+					if !utils::URL_VARIABLES.contains(&variable.as_str()) {
+						self.push(
+							file,
+							pointer,
+							format!(
+								"Unknown variable {{{}}} in {} (known variables: {:?})",
+								variable,
+								template,
+								utils::URL_VARIABLES
+							),
+						);
+					}
+				}
+			},
+			None => self.push(file, pointer, format!("Malformed url template {}", template)),
+		}
+	}
+
+	fn check_refs(&mut self) {
+		let mut refs = Vec::new();
+		for (file, json) in &self.files {
+			collect_refs(json, "", &mut |pointer, reference| {
+				refs.push((file.clone(), pointer, reference.to_string()));
+			});
+		}
+
+		for (file, pointer, reference) in refs {
+			if let Err(message) = self.resolve_ref(&file, &reference) {
+				self.push(&file, &pointer, format!("Unresolved $ref {}: {}", reference, message));
+			}
+		}
+	}
+
+	fn resolve_ref(&self, file: &Path, reference: &str) -> Result<(), String> {
+		let (target, fragment) = reference.split_once('#').unwrap_or((reference, ""));
+
+		let document = if target.is_empty() {
+			self.files.get(file).cloned()
+		}
+		else if target.starts_with("http://") || target.starts_with("https://") {
+			let document = Url::parse(target)
+				.ok()
+				.and_then(|url| self.resolver.find(&url))
+				.map(|document| (*document).clone());
+			if document.is_none() {
+				return Err(format!("{} is not available in the framework or the model", target));
+			}
+			document
+		}
+		else {
+			let path = utils::normalize_path(&file.parent().unwrap_or_else(|| Path::new("")).join(target));
+			let document = self.files.get(&path).cloned();
+			if document.is_none() {
+				return Err(format!("{:?} not found in the model", path));
+			}
+			document
+		};
+
+		match document {
+			Some(document) if fragment.starts_with('/') && document.pointer(fragment).is_none() => {
+				Err(format!("{} not found", fragment))
+			},
+			Some(_) => Ok(()),
+			None => Err(String::from("document not found")),
+		}
+	}
+}
+
+fn collect_refs(json: &Json, pointer: &str, found: &mut impl FnMut(String, &str)) {
+	match json {
+		Json::Object(object) => {
+			for (key, value) in object {
+				let pointer = format!("{}/{}", pointer, utils::pointer_token(key));
+				match value {
+					Json::String(reference) if key == "$ref" => found(pointer, reference),
+					value => collect_refs(value, &pointer, found),
+				}
+			}
+		},
+		Json::Array(array) => {
+			for (i, value) in array.iter().enumerate() {
+				collect_refs(value, &format!("{}/{}", pointer, i), found);
+			}
+		},
+		_ => (),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+
+	use std::path::{Path, PathBuf};
+
+	use serde_json::json;
+	use tempfile::TempDir;
+	use url::Url;
+
+	use crate::framework::Framework;
+	use crate::lint::Linter;
+	use crate::source::Source;
+	use crate::Json;
+
+	/// Directory with the files, which must be kept until the source is fetched
+	fn source(files: &[(&str, String)]) -> (TempDir, Source) {
+		let dir = tempfile::tempdir().unwrap();
+		for (path, contents) in files {
+			let path = dir.path().join(path);
+			std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+			std::fs::write(path, contents).unwrap();
+		}
+		let source = Source::new(&Url::from_directory_path(dir.path()).unwrap(), None, None);
+		(dir, source)
+	}
+
+	fn framework() -> Framework {
+		let mut files = [
+			"beaconInfoResponse",
+			"beaconMapResponse",
+			"beaconConfigurationResponse",
+			"beaconEntryTypesResponse",
+			"beaconBooleanResponse",
+			"beaconCountResponse",
+			"beaconResultsetsResponse",
+			"beaconCollectionsResponse",
+		]
+		.iter()
+		.map(|name| (format!("responses/{}.json", name), json!({})))
+		.collect::<Vec<_>>();
+		files.push((
+			String::from("configuration/beaconConfigurationSchema.json"),
+			json!({"required": ["entryTypes"]}),
+		));
+		files.push((
+			String::from("configuration/beaconMapSchema.json"),
+			json!({"required": ["endpointSets"]}),
+		));
+		files.push((String::from("configuration/endpointsSchema.json"), json!({})));
+		let files = files
+			.iter()
+			.map(|(path, json)| (path.as_str(), json.to_string()))
+			.collect::<Vec<_>>();
+		let (_dir, source) = source(&files);
+		Framework::load(source).unwrap()
+	}
+
+	/// Model with an individual entry type and its endpoints, with `replace` replacing some of its files
+	fn lint(replace: &[(&str, Json)]) -> Vec<(PathBuf, String, String)> {
+		let mut files = vec![
+			(
+				"beaconConfiguration.json",
+				json!({"entryTypes": {"individual": {
+					"id": "individual",
+					"defaultSchema": {"referenceToSchemaDefinition": "./individuals/defaultSchema.json"}
+				}}}),
+			),
+			(
+				"beaconMap.json",
+				json!({"endpointSets": {"individual": {
+					"entryType": "individual",
+					"rootUrl": "https://example.org/api/individuals",
+					"singleEntryUrl": "https://example.org/api/individuals/{id}",
+					"endpoints": {"individual": {
+						"returnedEntryType": "individual",
+						"url": "https://example.org/api/individuals/{id}/individuals"
+					}}
+				}}}),
+			),
+			("endpoints.json", json!({})),
+			(
+				"individuals/defaultSchema.json",
+				json!({"properties": {"sex": {"$ref": "../common/ontologyTerm.json#/definitions/OntologyTerm"}}}),
+			),
+			(
+				"common/ontologyTerm.json",
+				json!({"definitions": {"OntologyTerm": {"type": "object"}}}),
+			),
+		];
+		for (path, json) in replace {
+			files.retain(|(file, _)| file != path);
+			files.push((path, json.clone()));
+		}
+		let files = files
+			.iter()
+			.map(|(path, json)| (*path, json.to_string()))
+			.collect::<Vec<_>>();
+		let (_dir, source) = source(&files);
+		Linter::load(source)
+			.unwrap()
+			.lint(&framework())
+			.findings
+			.into_iter()
+			.map(|finding| (finding.file, finding.pointer, finding.message))
+			.collect()
+	}
+
+	fn finding(file: &str, pointer: &str, message: &str) -> (PathBuf, String, String) {
+		(Path::new(file).to_path_buf(), pointer.to_string(), message.to_string())
+	}
+
+	#[test]
+	fn test_lint_valid() {
+		assert_eq!(lint(&[]), Vec::new());
+	}
+
+	#[test]
+	fn test_lint_against_schema() {
+		assert_eq!(
+			lint(&[("beaconMap.json", json!({"endpoints": {}}))]),
+			vec![finding("beaconMap.json", "", "\"endpointSets\" is a required property")]
+		);
+	}
+
+	#[test]
+	fn test_lint_missing_file() {
+		let (_dir, source) = source(&[]);
+		let mut linter = Linter::load(source).unwrap();
+		linter.check_against_schema("endpoints.json", &json!({}));
+		assert_eq!(linter.findings.len(), 1);
+		assert_eq!(linter.findings[0].message, "endpoints.json not found");
+	}
+
+	#[test]
+	fn test_lint_entry_types() {
+		let configuration = json!({"entryTypes": {
+			"individual": {"id": "individual", "defaultSchema": {"referenceToSchemaDefinition": "./individuals/missing.json"}},
+			"biosample": {"id": "biosample"}
+		}});
+		assert_eq!(
+			lint(&[("beaconConfiguration.json", configuration)]),
+			vec![
+				finding(
+					"beaconConfiguration.json",
+					"/entryTypes/biosample",
+					"Entry type biosample has no default schema"
+				),
+				finding(
+					"beaconConfiguration.json",
+					"/entryTypes/individual/defaultSchema/referenceToSchemaDefinition",
+					"Schema ./individuals/missing.json of entry type individual not found"
+				),
+			]
+		);
+	}
+
+	#[test]
+	fn test_lint_endpoint_sets() {
+		let beacon_map = json!({"endpointSets": {"individual": {
+			"entryType": "person",
+			"rootUrl": "https://example.org/api/individuals",
+			"endpoints": {"biosample": {
+				"returnedEntryType": "biosample",
+				"url": "https://example.org/api/individuals/{id}/biosamples"
+			}}
+		}}});
+		assert_eq!(
+			lint(&[("beaconMap.json", beacon_map)]),
+			vec![
+				finding(
+					"beaconMap.json",
+					"/endpointSets/individual/entryType",
+					"Entry type person is not declared in beaconConfiguration.json (it has no schema)"
+				),
+				finding(
+					"beaconMap.json",
+					"/endpointSets/individual/endpoints/biosample/returnedEntryType",
+					"Related endpoint returns entry type biosample which is not declared in beaconConfiguration.json"
+				),
+			]
+		);
+	}
+
+	#[test]
+	fn test_lint_url_variables() {
+		let beacon_map = json!({"endpointSets": {"individual": {
+			"entryType": "individual",
+			"rootUrl": "https://example.org/api/individuals{?skip,limit}",
+			"singleEntryUrl": "https://example.org/api/individuals/{individualId}",
+			"filteringTermsUrl": "https://example.org/api/individuals/{id/filtering_terms"
+		}}});
+		let findings = lint(&[("beaconMap.json", beacon_map)]);
+		assert_eq!(findings.len(), 2);
+		assert_eq!(
+			(&findings[0].1, &findings[1].1),
+			(
+				&String::from("/endpointSets/individual/singleEntryUrl"),
+				&String::from("/endpointSets/individual/filteringTermsUrl")
+			)
+		);
+		assert!(findings[0]
+			.2
+			.starts_with("Unknown variable {individualId} in https://example.org/api/individuals/{individualId}"));
+		assert_eq!(
+			findings[1].2,
+			"Malformed url template https://example.org/api/individuals/{id/filtering_terms"
+		);
+	}
+
+	#[test]
+	fn test_lint_refs() {
+		let schema = json!({"properties": {
+			"sex": {"$ref": "../common/ontologyTerm.json#/definitions/Missing"},
+			"age": {"$ref": "../common/age.json"},
+			"diseases": {"items": [{"$ref": "https://example.org/schemas/disease.json"}]},
+			"local": {"$ref": "#/properties/sex"}
+		}});
+		assert_eq!(
+			lint(&[("individuals/defaultSchema.json", schema)]),
+			vec![
+				finding(
+					"individuals/defaultSchema.json",
+					"/properties/age/$ref",
+					"Unresolved $ref ../common/age.json: \"common/age.json\" not found in the model"
+				),
+				finding(
+					"individuals/defaultSchema.json",
+					"/properties/diseases/items/0/$ref",
+					"Unresolved $ref https://example.org/schemas/disease.json: https://example.org/schemas/disease.json \
+					 is not available in the framework or the model"
+				),
+				finding(
+					"individuals/defaultSchema.json",
+					"/properties/sex/$ref",
+					"Unresolved $ref ../common/ontologyTerm.json#/definitions/Missing: /definitions/Missing not found"
+				),
+			]
+		);
+	}
+
+	#[test]
+	fn test_lint_bad_json() {
+		let (_dir, source) = source(&[("beaconMap.json", String::from("{"))]);
+		let linter = Linter::load(source).unwrap();
+		assert_eq!(linter.findings.len(), 1);
+		assert_eq!(linter.findings[0].file, Path::new("beaconMap.json"));
+		assert!(linter.findings[0].message.starts_with("Bad json: "));
+	}
+}
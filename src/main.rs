@@ -6,40 +6,87 @@
 )]
 
 use std::collections::BTreeMap;
+use std::path::PathBuf;
 
 use chrono::SubsecRound;
-use clap::Parser;
+use clap::error::ErrorKind;
+use clap::{CommandFactory, Parser};
+use reqwest::blocking::Client;
+use serde::Serialize;
 use url::Url;
 
 use crate::beacon::Beacon;
+use crate::cache::Cache;
+use crate::checks::{Check, Severity};
+use crate::data::DataFiles;
 use crate::framework::Framework;
+use crate::lint::Linter;
 use crate::model::Model;
-use crate::output::BeaconOutput;
+use crate::output::{BeaconOutput, Summary};
+use crate::profiles::Profile;
+use crate::releases::{Release, Releases};
+use crate::sampling::Sampling;
+use crate::snapshot::Snapshot;
+use crate::source::Source;
+use crate::suites::Suite;
+use crate::v1::BeaconV1;
+use crate::waivers::Waivers;
 
 mod beacon;
+mod cache;
+mod checks;
+mod consistency;
+mod data;
 mod endpoint;
 mod error;
 mod framework;
+mod handovers;
+mod integrity;
 mod interface;
+mod lint;
 mod model;
+mod negative;
 mod output;
+mod profiles;
+mod releases;
+mod resolver;
+mod sampling;
+mod schemas;
+mod service_info;
+mod snapshot;
+mod source;
+mod suites;
 mod utils;
+mod v1;
+mod waivers;
 
 pub type Json = serde_json::Value;
 
+const DEFAULT_MODEL: &str = "https://github.com/MrRobb/beacon-v2-Models/BEACON-V2-draft4-Model";
+const DEFAULT_FRAMEWORK: &str = "https://github.com/MrRobb/beacon-framework-v2";
+
 #[derive(clap::Parser)]
-#[clap(about, version, author)]
+#[clap(
+	about,
+	version,
+	author,
+	subcommand_negates_reqs = true,
+	args_conflicts_with_subcommands = true
+)]
 struct Args {
+	#[clap(subcommand)]
+	command: Option<Command>,
+
 	/// Sets the level of verbosity
-	#[clap(short, long, conflicts_with("quiet"))]
+	#[clap(short, long, conflicts_with("quiet"), global = true)]
 	verbose: bool,
 
 	/// Do not print any logs
-	#[clap(short, long, conflicts_with("summary"))]
+	#[clap(short, long, conflicts_with("summary"), global = true)]
 	quiet: bool,
 
 	/// Only log the summary of the results, do not output anything
-	#[clap(short, long, conflicts_with("verbose"))]
+	#[clap(short, long, conflicts_with("verbose"), global = true)]
 	summary: bool,
 
 	/// Only validate the framework referenced
@@ -50,22 +97,363 @@ struct Args {
 	#[clap(
 		short,
 		long,
-		default_value = "https://github.com/MrRobb/beacon-v2-Models/BEACON-V2-draft4-Model"
+		default_value = DEFAULT_MODEL,
+		global = true
 	)]
 	model: Url,
 
+	/// Git branch, tag or commit of the model (it can also be given as `<repo>@<ref>` in the location)
+	#[clap(long = "model-ref", global = true)]
+	model_ref: Option<String>,
+
+	/// Subdirectory of the model inside its git repository
+	#[clap(long = "model-path", global = true)]
+	model_path: Option<PathBuf>,
+
 	/// Location of the framework
-	#[clap(short, long, default_value = "https://github.com/MrRobb/beacon-framework-v2")]
+	#[clap(
+		short,
+		long,
+		default_value = DEFAULT_FRAMEWORK,
+		global = true
+	)]
 	framework: Url,
 
+	/// Git branch, tag or commit of the framework (it can also be given as `<repo>@<ref>` in the location)
+	#[clap(long = "framework-ref", global = true)]
+	framework_ref: Option<String>,
+
+	/// Subdirectory of the framework inside its git repository
+	#[clap(long = "framework-path", global = true)]
+	framework_path: Option<PathBuf>,
+
 	/// Url to the Beacon implementation
-	url: Url,
+	#[clap(required = true)]
+	url: Option<Url>,
+
+	/// Directory where the git repositories of models and frameworks are cached [default: ~/.cache/beacon-verifier]
+	#[clap(long = "cache-dir", global = true)]
+	cache_dir: Option<PathBuf>,
+
+	/// Seconds after which a cached branch is fetched again (tags and commits are never fetched again)
+	#[clap(long = "cache-max-age", default_value = "3600", global = true)]
+	cache_max_age: i64,
+
+	/// Only use the cached git repositories, never fetch them (nor the schemas advertised by the beacon)
+	#[clap(long, conflicts_with("no_cache"), global = true)]
+	offline: bool,
+
+	/// Do not cache the git repositories
+	#[clap(long = "no-cache", global = true)]
+	no_cache: bool,
+
+	/// Also validate the entry types of the beacon map that are not in the model, with the schemas advertised in
+	/// /entry_types
+	#[clap(long, conflicts_with("only_framework"))]
+	discover: bool,
+
+	/// Ids used to test the single entry and related endpoints
+	#[clap(long, value_enum, default_value = "first")]
+	sample: SampleStrategy,
+
+	/// Number of ids used by the first and random sampling strategies
+	#[clap(long = "sample-size", default_value = "1")]
+	sample_size: usize,
+
+	/// Seed of the random sampling strategy [default: random]
+	#[clap(long)]
+	seed: Option<u64>,
+
+	/// Conformance profile of the beacon [default: the securityAttributes.defaultGranularity of /configuration, or
+	/// record]
+	#[clap(long, value_enum)]
+	profile: Option<Profile>,
+
+	/// Do not request the handovers outside the host of the beacon
+	#[clap(long = "skip-external-handovers")]
+	skip_external_handovers: bool,
+
+	/// Location of the ids in the records of an entry type, as `<entry type>=<field or JSON pointer>` (e.g.
+	/// `genomicVariation=variantInternalId`). By default it is guessed from the schema of the entry type.
+	#[clap(long = "id-field", value_parser = parse_id_field, global = true)]
+	id_fields: Vec<(String, String)>,
+
+	/// JSON file with the framework and model of each release of the specification, instead of the releases embedded
+	/// in the verifier. The release is chosen with the apiVersion of /info, and it is only used instead of the default
+	/// framework and model.
+	#[clap(long, global = true)]
+	releases: Option<PathBuf>,
+
+	/// Spec version used to choose the release instead of the apiVersion of /info (e.g. `v2.0.0`)
+	#[clap(long = "spec-version", value_parser = releases::parse_version, global = true)]
+	spec_version: Option<semver::Version>,
+
+	/// Severity of the failures of a check, as `<check>=<severity>` (e.g. `service_info=info`). By default it is
+	/// error for the MUST requirements of the specification and warning for the SHOULD ones.
+	#[clap(long = "severity", value_parser = parse_severity)]
+	severities: Vec<(Check, Severity)>,
+
+	/// JSON file with the known deviations of the beacons, which are reported but do not fail the validation
+	#[clap(long)]
+	waivers: Option<PathBuf>,
+
+	/// Exit with a non-zero status code if a check that is not waived fails with this severity or a higher one
+	#[clap(long = "fail-on", value_enum)]
+	fail_on: Option<Severity>,
+
+	/// YAML or JSON file with queries and their expected answers, run against the beacon (it can be repeated)
+	#[clap(long = "suite", conflicts_with("only_framework"))]
+	suites: Vec<PathBuf>,
 
 	/// Skip tls/ssl cert validation
 	#[clap(long = "ssl-no-verify")]
 	ssl_no_verify: bool,
 }
 
+#[derive(Clone, Copy, clap::ValueEnum)]
+enum SampleStrategy {
+	/// The first ids returned
+	First,
+	/// Ids picked at random
+	Random,
+	/// All the ids returned
+	All,
+}
+
+fn parse_id_field(value: &str) -> Result<(String, String), String> {
+	let (entry_type, field) = value
+		.split_once('=')
+		.ok_or_else(|| format!("{} is not <entry type>=<field or JSON pointer>", value))?;
+	Ok((entry_type.to_string(), utils::id_pointer(field)))
+}
+
+fn parse_severity(value: &str) -> Result<(Check, Severity), String> {
+	let (check, severity) = value
+		.split_once('=')
+		.ok_or_else(|| format!("{} is not <check>=<severity>", value))?;
+	let check = serde_json::from_value(Json::String(check.to_string()))
+		.map_err(|_| format!("{} is not a check", check))?;
+	let severity = serde_json::from_value(Json::String(severity.to_string()))
+		.map_err(|_| format!("{} is not info, warning or error", severity))?;
+	Ok((check, severity))
+}
+
+impl Args {
+	fn id_pointers(&self) -> BTreeMap<String, String> {
+		self.id_fields.iter().cloned().collect()
+	}
+
+	fn waivers(&self) -> Waivers {
+		self.waivers
+			.as_ref()
+			.map(|path| Waivers::load(path).expect("Loading waivers failed"))
+			.unwrap_or_default()
+	}
+
+	/// Applies `--severity` and `--waivers` to the output of a Beacon v1 or of the data files, which are not
+	/// validated by [`Beacon`]
+	fn judge(&self, output: &mut BeaconOutput) {
+		let beacon = [output.url.to_string(), output.name.clone()];
+		let beacon = beacon.iter().map(String::as_str).collect::<Vec<_>>();
+		BeaconOutput::judge(
+			&mut output.entities,
+			&beacon,
+			&self.severities.iter().copied().collect(),
+			&self.waivers(),
+		);
+	}
+
+	/// Exits with an error if the options that only apply to Beacon v2 implementations are given
+	fn reject_v2_options(&self, validated: &str) {
+		for (option, given) in [("--profile", self.profile.is_some()), ("--suite", !self.suites.is_empty())] {
+			if given {
+				Self::command()
+					.error(
+						ErrorKind::ArgumentConflict,
+						format!("{} cannot be used with {}", option, validated),
+					)
+					.exit();
+			}
+		}
+	}
+
+	fn suites(&self) -> Vec<Suite> {
+		self.suites
+			.iter()
+			.map(|path| Suite::load(path).expect("Loading test suite failed"))
+			.collect()
+	}
+
+	fn sampling(&self) -> Sampling {
+		match self.sample {
+			SampleStrategy::First => Sampling::First(self.sample_size),
+			SampleStrategy::Random => {
+				let seed = self.seed.unwrap_or_else(rand::random);
+				log::info!("Sampling {} ids at random with seed {}", self.sample_size, seed);
+				Sampling::Random {
+					size: self.sample_size,
+					seed,
+				}
+			},
+			SampleStrategy::All => Sampling::All,
+		}
+	}
+
+	fn cache(&self) -> Option<Cache> {
+		if self.no_cache {
+			None
+		}
+		else {
+			Some(Cache::new(self.cache_dir.clone(), self.cache_max_age, self.offline))
+		}
+	}
+
+	/// The embedded snapshots are only used instead of the default locations
+	fn fallback(location: &Url, default: &str, reference: &Option<String>, path: &Option<PathBuf>) -> bool {
+		location.as_str() == default && reference.is_none() && path.is_none()
+	}
+
+	/// Release of the specification whose framework and model are used, chosen with the spec version of the beacon
+	fn release(&self, client: &Client) -> Option<Release> {
+		let releases = match &self.releases {
+			Some(path) => Releases::load(path).expect("Loading releases failed"),
+			None => Releases::embedded(),
+		};
+		let version = match (&self.spec_version, &self.url) {
+			(Some(version), _) => version.clone(),
+			(None, Some(url)) => match releases::claimed_version(client, url) {
+				Ok(Some(version)) => version,
+				Ok(None) => {
+					log::warn!("No apiVersion in {}/info, using the default framework and model", url);
+					return None;
+				},
+				Err(e) => {
+					log::warn!(
+						"The spec version of the beacon is unknown ({}), using the default framework and model",
+						e
+					);
+					return None;
+				},
+			},
+			(None, None) => return None,
+		};
+		let release = releases.find(&version).cloned();
+		match &release {
+			Some(release) => log::info!("Using release {} for spec version {}", release.release, version),
+			None => log::warn!(
+				"No release matches spec version {}, using the default framework and model",
+				version
+			),
+		}
+		release
+	}
+
+	fn model_source(&self, client: &Client, release: Option<&Release>) -> Source {
+		let fallback = Self::fallback(&self.model, DEFAULT_MODEL, &self.model_ref, &self.model_path);
+		if let Some(release) = release.filter(|_| fallback) {
+			return Source::new(&release.model, None, release.model_path.clone())
+				.cache(self.cache())
+				.client(client);
+		}
+		Source::new(&self.model, self.model_ref.clone(), self.model_path.clone())
+			.cache(self.cache())
+			.client(client)
+			.fallback(Some(Snapshot::Model).filter(|_| fallback))
+	}
+
+	fn framework_source(&self, client: &Client, release: Option<&Release>) -> Source {
+		let fallback = Self::fallback(&self.framework, DEFAULT_FRAMEWORK, &self.framework_ref, &self.framework_path);
+		if let Some(release) = release.filter(|_| fallback) {
+			return Source::new(&release.framework, None, release.framework_path.clone())
+				.cache(self.cache())
+				.client(client);
+		}
+		Source::new(&self.framework, self.framework_ref.clone(), self.framework_path.clone())
+			.cache(self.cache())
+			.client(client)
+			.fallback(Some(Snapshot::Framework).filter(|_| fallback))
+	}
+}
+
+#[derive(clap::Subcommand)]
+enum Command {
+	/// Validate local data files (JSON or JSON Lines) against the model
+	///
+	/// The entry type of every file is taken from its name (e.g. `individuals.json`).
+	ValidateData {
+		/// Data files to validate
+		#[clap(required = true)]
+		files: Vec<PathBuf>,
+	},
+
+	/// Check the consistency of the model against the framework
+	///
+	/// Exits with a non-zero status code if any problem is found.
+	Lint,
+
+	/// Manage the cache of git repositories
+	Cache {
+		#[clap(subcommand)]
+		action: CacheCommand,
+	},
+}
+
+#[derive(clap::Subcommand)]
+enum CacheCommand {
+	/// List the cached repositories
+	List,
+
+	/// Remove the cached branches older than `--cache-max-age` (tags and commits are kept)
+	Prune {
+		/// Remove all the cached repositories
+		#[clap(long)]
+		all: bool,
+	},
+
+	/// Fetch all the cached repositories again
+	Refresh,
+}
+
+/// The `$ref`s of the model are resolved with the files of the framework and the model
+fn load_model(source: Source, framework: &Framework, id_pointers: BTreeMap<String, String>) -> Model {
+	log::debug!("Loading model from: {}", source.location);
+	let mut model = Model::load(source, framework.resolver.clone()).expect("Loading model failed");
+	model.set_id_pointers(id_pointers);
+	log::info!("Number of entities of the model: {}", model.entities.len());
+	model
+}
+
+fn print_output<T: Serialize + Summary>(output: &T, matches: &Args) {
+	if matches.summary {
+		log::set_max_level(log::LevelFilter::Trace);
+		output.summary();
+	}
+	else {
+		if !matches.quiet {
+			eprintln!();
+		}
+		output.summary();
+		let payload = serde_json::to_string_pretty(output).unwrap();
+		println!("{}", payload);
+	}
+}
+
+/// Exits with a non-zero status code if a check that is not waived failed with the `--fail-on` severity or a higher one
+fn exit_on_failures(output: &BeaconOutput, matches: &Args) {
+	if let Some(fail_on) = matches.fail_on {
+		let failures = output
+			.entities
+			.values()
+			.flatten()
+			.filter(|report| report.failure().map_or(false, |severity| severity >= fail_on))
+			.count();
+		if failures > 0 {
+			log::error!("{} checks failed with severity {:?} or higher", failures, fail_on);
+			std::process::exit(1);
+		}
+	}
+}
+
 fn main() {
 	// Get args
 	let matches = Args::parse();
@@ -84,46 +472,132 @@ fn main() {
 		pretty_env_logger::init();
 	}
 
+	if let Some(Command::Cache { action }) = &matches.command {
+		let cache = Cache::new(matches.cache_dir.clone(), matches.cache_max_age, matches.offline);
+		let entries = match action {
+			CacheCommand::List => cache.entries(),
+			CacheCommand::Prune { all } => cache.prune(*all).expect("Pruning the cache failed"),
+			CacheCommand::Refresh => cache.refresh().expect("Refreshing the cache failed"),
+		};
+		log::info!("{} cached repositories", entries.len());
+		println!("{}", serde_json::to_string_pretty(&entries).unwrap());
+		return;
+	}
+
+	let client = utils::client(matches.ssl_no_verify).expect("Building the HTTP client failed");
+
+	// Beacon v1
+	if let (None, Some(beacon_url)) = (&matches.command, &matches.url) {
+		if let Some(beacon) = BeaconV1::detect(&client, beacon_url) {
+			log::info!("Validating Beacon v1 implementation on {}", beacon_url);
+			matches.reject_v2_options("a Beacon v1 implementation");
+			let mut output = beacon.validate();
+			matches.judge(&mut output);
+			print_output(&output, &matches);
+			exit_on_failures(&output, &matches);
+			return;
+		}
+	}
+
+	// Choose release
+	let release = matches.release(&client);
+
 	// Load framework
-	let framework_location = matches.framework;
-	log::debug!("Loading framework from: {}", &framework_location);
-	let framework = Framework::load(&framework_location).expect("Loading framework failed");
+	let framework_source = matches.framework_source(&client, release.as_ref());
+	log::debug!("Loading framework from: {}", &framework_source.location);
+	let framework = Framework::load(framework_source).expect("Loading framework failed");
 	log::debug!("Framework loaded");
 
+	if let Some(Command::ValidateData { files }) = &matches.command {
+		matches.reject_v2_options("validate-data");
+		let model = load_model(matches.model_source(&client, release.as_ref()), &framework, matches.id_pointers());
+		let mut output = DataFiles::new(model, files.clone()).validate();
+		output.release = release.map(|release| release.release);
+		matches.judge(&mut output);
+		print_output(&output, &matches);
+		exit_on_failures(&output, &matches);
+		return;
+	}
+
+	if let Some(Command::Lint) = &matches.command {
+		log::info!("Linting model {}", matches.model);
+		let output = Linter::load(matches.model_source(&client, release.as_ref()))
+			.expect("Loading model failed")
+			.lint(&framework);
+		print_output(&output, &matches);
+		if !output.findings.is_empty() {
+			std::process::exit(1);
+		}
+		return;
+	}
+
 	// Load model
 	let model = if matches.only_framework {
 		None
 	} else {
-		let model_location = matches.model;
-		log::debug!("Loading model from: {}", model_location);
-		let model = Model::load(&model_location).expect("Loading model failed");
-		log::info!("Number of entities of the model: {}", model.entities.len());
-		Some(model)
+		Some(load_model(matches.model_source(&client, release.as_ref()), &framework, matches.id_pointers()))
 	};
 
 	// Load beacon
-	let beacon_url = matches.url;
+	let beacon_url = matches.url.clone().expect("No beacon url");
 	log::info!("Validating implementation on {}", beacon_url);
 
-	let output = match Beacon::new(model, framework, &beacon_url) {
-		Ok(beacon) => beacon.validate(),
+	let model_source = model.as_ref().map(|model| model.source.clone());
+	let framework_source = framework.source.clone();
+	let mut output = match Beacon::new(&client, model, framework, &beacon_url) {
+		Ok(beacon) => beacon
+			.discover(matches.discover)
+			.offline(matches.offline)
+			.sampling(matches.sampling())
+			.skip_external_handovers(matches.skip_external_handovers)
+			.profile(matches.profile)
+			.severities(matches.severities.iter().copied().collect())
+			.waivers(matches.waivers())
+			.suites(matches.suites())
+			.validate(),
 		Err(e) => BeaconOutput {
 			name: format!("Unknown Beacon ({})", e),
 			url: beacon_url,
 			last_updated: chrono::offset::Utc::now().naive_utc().round_subsecs(6),
+			model: model_source,
+			framework: Some(framework_source),
+			spec_version: None,
+			release: None,
+			handovers: BTreeMap::new(),
+			profile: None,
+			profiles: BTreeMap::new(),
+			requirements: Vec::new(),
 			entities: BTreeMap::new(),
 		},
 	};
+	output.release = release.map(|release| release.release);
 
-	if matches.summary {
-		log::set_max_level(log::LevelFilter::Trace);
-		output.summary();
-	} else {
-		if !matches.quiet {
-			eprintln!();
-		}
-		output.summary();
-		let payload = serde_json::to_string_pretty(&output).unwrap();
-		println!("{}", payload);
+	print_output(&output, &matches);
+	exit_on_failures(&output, &matches);
+}
+
+#[cfg(test)]
+mod tests {
+
+	use crate::parse_id_field;
+
+	#[test]
+	fn test_parse_id_field() {
+		assert_eq!(
+			parse_id_field("individual=individualId"),
+			Ok((String::from("individual"), String::from("/individualId")))
+		);
+		assert_eq!(
+			parse_id_field("biosample=/info/sampleId"),
+			Ok((String::from("biosample"), String::from("/info/sampleId")))
+		);
+		assert_eq!(
+			parse_id_field("run=run/id"),
+			Ok((String::from("run"), String::from("/run~1id")))
+		);
+		assert_eq!(
+			parse_id_field("individualId"),
+			Err(String::from("individualId is not <entry type>=<field or JSON pointer>"))
+		);
 	}
 }
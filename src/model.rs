@@ -4,23 +4,33 @@ use std::fs::File;
 use std::path::{Path, PathBuf};
 use std::rc::Rc;
 
-use git2::Repository;
 use jsonschema::JSONSchema;
+use reqwest::blocking::Client;
 use url::Url;
 
+use crate::checks::Check;
 use crate::endpoint::BeaconEndpoint;
 use crate::error::VerifierError;
+use crate::integrity::{Integrity, Reference};
 use crate::interface::{Endpoint, EntryType, RelatedEndpoint};
-use crate::utils::replace_vars;
+use crate::output::EndpointReport;
+use crate::resolver::LocalResolver;
+use crate::sampling::Sampling;
+use crate::source::Source;
 use crate::{utils, Json};
 
 #[derive(Debug, Clone)]
 pub struct Entity {
+	pub entry_type: String,
 	pub name: String,
-	pub url: Url,
-	pub url_single: Option<Url>,
+	/// Url templates (RFC 6570)
+	pub url: String,
+	pub url_single: Option<String>,
 	pub schema: Rc<JSONSchema>,
-	pub filtering_terms_url: Option<Url>,
+	/// JSON pointer to the id of the records
+	pub id_pointer: String,
+	#[allow(dead_code)] // TODO: Filtering terms
+	pub filtering_terms_url: Option<String>,
 	pub related_endpoints: Option<BTreeMap<String, RelatedEndpoint>>,
 }
 
@@ -32,40 +42,16 @@ pub struct Model {
 	pub beacon_map_json: Json,
 	pub endpoints_json: Json,
 	files: BTreeMap<PathBuf, Json>,
+	pub source: Source,
+	pub resolver: LocalResolver,
+	/// JSON pointers to the ids of the records by entry type, instead of the ones guessed from the schemas
+	id_pointers: BTreeMap<String, String>,
 }
 
 impl Model {
-	pub fn load(location: &Url) -> Result<Self, VerifierError> {
-		let dir = tempfile::tempdir().expect("Could not create temporary directory");
-
-		if location.scheme() == "file" {
-			log::debug!("COPYING {} to {:?}", location.path(), dir.path());
-			utils::copy_dir_recursively(location.path(), &dir).expect("Copy dir recursively failed");
-		}
-		else {
-			// Parse model repo URL
-			assert_eq!(
-				location.domain().unwrap_or(""),
-				"github.com",
-				"Only repos hosted on github.com are supported"
-			);
-			let mut url_iter = Path::new(location.path()).components().skip(1);
-			let owner = url_iter.next().unwrap().as_os_str().to_string_lossy().to_string();
-			let repo = url_iter.next().unwrap().as_os_str().to_string_lossy().to_string();
-			let path: PathBuf = url_iter.collect();
-
-			log::debug!("Downloading repo {} from {}", repo, owner);
-			log::debug!("Path inside repo = {:?}", path);
-
-			// Clone repo to tempdir
-			let repo_url = format!("https://github.com/{owner}/{repo}", owner = owner, repo = repo);
-			let full_git_dir = tempfile::tempdir().expect("Could not create temporary directory");
-			Repository::clone(&repo_url, full_git_dir.path()).expect("Unable to clone repository");
-
-			// Copy subfolder to the final tempdir
-			log::debug!("Copying from {:?} to {:?}", full_git_dir.path().join(&path), dir.path());
-			utils::copy_dir_recursively(full_git_dir.path().join(path), dir.path()).unwrap();
-		}
+	/// The resolver contains the files of the framework, the ones of the model are added to it
+	pub fn load(mut source: Source, resolver: LocalResolver) -> Result<Self, VerifierError> {
+		let dir = source.fetch()?;
 
 		let mut model = Self {
 			entities: Vec::new(),
@@ -74,6 +60,9 @@ impl Model {
 			beacon_map_json: Json::Null,
 			endpoints_json: Json::Null,
 			files: BTreeMap::new(),
+			source,
+			resolver,
+			id_pointers: BTreeMap::new(),
 		};
 
 		// Load files
@@ -83,6 +72,10 @@ impl Model {
 			}
 		}
 
+		model
+			.resolver
+			.add_files("model", &model.source.location, dir.path(), &model.files);
+
 		// Load configuration
 		model.load_configuration(dir.path());
 
@@ -118,6 +111,22 @@ impl Model {
 			.clone();
 	}
 
+	/// Location of a `referenceToSchemaDefinition` inside the model directory
+	pub fn schema_path(base_path: &Path, reference: &str) -> PathBuf {
+		let mut schema_rel_path = reference.to_string();
+		if schema_rel_path.starts_with("http") {
+			let schema_rel_path_url = Url::parse(&schema_rel_path).unwrap();
+			schema_rel_path = Path::new(schema_rel_path_url.path())
+				.components()
+				.skip(1)
+				.collect::<PathBuf>()
+				.to_string_lossy()
+				.to_string();
+		}
+		log::debug!("Loading schema on {:?} + {:?}", base_path, schema_rel_path);
+		base_path.join(schema_rel_path)
+	}
+
 	fn load_entities(&mut self, base_path: &Path) {
 		let mut entities_names = BTreeMap::new();
 
@@ -128,29 +137,21 @@ impl Model {
 			.map(|(_, val)| {
 				let entry_type: EntryType = serde_json::from_value(val.clone()).unwrap();
 				entities_names.insert(entry_type.id.clone(), entry_type.name);
-				let mut schema_rel_path = entry_type.default_schema.reference_to_schema_definition;
-				if schema_rel_path.starts_with("http") {
-					let schema_rel_path_url = Url::parse(&schema_rel_path).unwrap();
-					schema_rel_path = Path::new(schema_rel_path_url.path())
-						.components()
-						.skip(1)
-						.collect::<PathBuf>()
-						.to_string_lossy()
-						.to_string();
-				}
-				log::debug!("Loading schema on {:?} + {:?}", base_path, schema_rel_path);
-				let schema_abs_path = base_path.join(schema_rel_path);
+				let schema_abs_path =
+					Self::schema_path(base_path, &entry_type.default_schema.reference_to_schema_definition);
 				log::debug!("Loading schema on {:?}", schema_abs_path);
 				let schema_file = File::open(schema_abs_path.canonicalize().unwrap()).expect("File not found");
 				let schema_json = serde_json::from_reader(schema_file).expect("Bad json schema");
-				(entry_type.id, schema_json)
+				let schema_rel_path = schema_abs_path.strip_prefix(base_path).unwrap_or(&schema_abs_path);
+				let schema_url = LocalResolver::base_url("model", &utils::normalize_path(schema_rel_path));
+				(entry_type.id, LocalResolver::with_base(&schema_json, &schema_url))
 			})
 			.collect::<BTreeMap<String, Json>>();
 
 		self.entities_names = entities_names;
 
-		for (_, entity) in self.beacon_map_json["endpointSets"].as_object().unwrap() {
-			let endpoint: Endpoint = serde_json::from_value(entity.clone()).unwrap();
+		for (_, entity) in self.beacon_map_json["endpointSets"].as_object().unwrap().clone() {
+			let endpoint: Endpoint = serde_json::from_value(entity).unwrap();
 			let entity_schema = entities_schemas
 				.get(&endpoint.entry_type)
 				.unwrap_or_else(|| {
@@ -162,64 +163,160 @@ impl Model {
 					panic!();
 				})
 				.clone();
-			self.entities.push(Entity {
-				schema: utils::compile_schema(&entity_schema),
-				name: self
-					.entities_names
-					.get(&endpoint.entry_type)
-					.unwrap_or(&String::from("Unknown entity name"))
-					.clone(),
-				url: endpoint.root_url,
-				url_single: endpoint.single_entry_url,
-				filtering_terms_url: endpoint.filtering_terms_url,
-				related_endpoints: endpoint.endpoints,
-			});
+			let schema = utils::compile_schema(&entity_schema, &self.resolver);
+			self.add_entity(endpoint, schema, utils::default_id_pointer(&entity_schema));
+		}
+	}
+
+	/// Overrides the location of the ids of the records of some entry types
+	pub fn set_id_pointers(&mut self, id_pointers: BTreeMap<String, String>) {
+		for entity in &mut self.entities {
+			if let Some(id_pointer) = id_pointers.get(&entity.entry_type) {
+				entity.id_pointer = id_pointer.clone();
+			}
 		}
+		self.id_pointers = id_pointers;
+	}
+
+	/// Adds the entity of an endpoint set, its entry type must be in `entities_names`
+	pub fn add_entity(&mut self, endpoint: Endpoint, schema: Rc<JSONSchema>, default_id_pointer: String) {
+		self.entities.push(Entity {
+			schema,
+			id_pointer: self
+				.id_pointers
+				.get(&endpoint.entry_type)
+				.cloned()
+				.unwrap_or(default_id_pointer),
+			name: self
+				.entities_names
+				.get(&endpoint.entry_type)
+				.unwrap_or(&String::from("Unknown entity name"))
+				.clone(),
+			entry_type: endpoint.entry_type,
+			url: endpoint.root_url,
+			url_single: endpoint.single_entry_url,
+			filtering_terms_url: endpoint.filtering_terms_url,
+			related_endpoints: endpoint.endpoints,
+		});
 	}
 
 	fn build_endpoint(
 		entity_name: String,
+		entry_type: String,
 		entity_schema: Rc<JSONSchema>,
 		name: String,
-		url: &Url,
-		vars: Vec<(&str, &str)>,
-	) -> BeaconEndpoint {
-		let replaced_url = replace_vars(url, vars);
-		BeaconEndpoint {
+		template: &str,
+		vars: &[(&str, &str)],
+	) -> Option<BeaconEndpoint> {
+		let url = utils::expand_template(template, vars).and_then(|url| Url::parse(&url).ok());
+		if url.is_none() {
+			log::error!("Bad url template {} of {}", template, name);
+		}
+		Some(BeaconEndpoint {
 			entity_name,
+			entry_type,
 			entity_schema,
 			name,
-			url: replaced_url,
-		}
+			template: template.to_string(),
+			url: url?,
+			integrity: Integrity::default(),
+			check: Check::Endpoint,
+		})
+	}
+
+	/// References from the records of an entity to the other entities with a single entry endpoint
+	fn references(&self, entity: &Entity) -> Vec<Reference> {
+		self.entities
+			.iter()
+			.filter(|other| other.entry_type != entity.entry_type)
+			.filter_map(|other| {
+				Some(Reference {
+					pointer: Self::reference_pointer(other),
+					entity_name: other.name.clone(),
+					template: other.url_single.clone()?,
+					id_pointer: other.id_pointer.clone(),
+				})
+			})
+			.collect()
 	}
 
-	pub fn endpoints(self, root_url: &Url) -> Vec<BeaconEndpoint> {
+	/// Location of the references to an entity in the records of the other ones (e.g. `/individualId`)
+	fn reference_pointer(entity: &Entity) -> String {
+		utils::id_pointer(&format!("{}Id", entity.entry_type))
+	}
+
+	/// The `{id}` urls are built with the ids of the main endpoint picked by `sampling`, one endpoint per id. The
+	/// entities whose ids could not be extracted have a report instead.
+	pub fn endpoints(
+		self,
+		client: &Client,
+		root_url: &Url,
+		sampling: &Sampling,
+	) -> Vec<Result<BeaconEndpoint, EndpointReport>> {
 		self.entities
 			.iter()
 			.flat_map(|entity| {
 				let mut endpoints = Vec::new();
 				let entity_schema = &entity.schema;
 
-				endpoints.push(Self::build_endpoint(
+				let all_entries = match Self::build_endpoint(
 					entity.name.clone(),
+					entity.entry_type.clone(),
 					entity_schema.clone(),
 					format!("{} all entries", entity.name.clone()),
 					&entity.url,
-					vec![],
-				));
+					&[],
+				) {
+					Some(all_entries) => all_entries,
+					None => return endpoints,
+				};
+				let ids = utils::get_ids(client, root_url, &all_entries.url, &entity.id_pointer);
+				let all_entries_url = utils::url_join(root_url, &all_entries.url);
+				endpoints.push(Ok(all_entries));
 
-				let ids = utils::get_ids(root_url, &entity.url);
+				let uses_ids = entity.url_single.is_some()
+					|| entity
+						.related_endpoints
+						.as_ref()
+						.map_or(false, |related| !related.is_empty());
+				match &ids {
+					Err(VerifierError::NoIds(id_pointer)) if uses_ids => {
+						let e = VerifierError::NoIds(id_pointer.clone());
+						log::warn!("{}: {}", entity.name, e);
+						endpoints.push(Err(EndpointReport::new(
+							&entity.name,
+							&format!("{} ids", entity.name),
+							all_entries_url,
+						)
+						.null(e)
+						.check(Check::Ids)));
+					},
+					_ => (),
+				}
 
 				if let Ok(ids) = ids {
+					let ids = sampling.sample(&ids);
 					if let Some(url_single) = &entity.url_single {
-						endpoints.extend(ids.iter().take(1).map(|id| {
+						endpoints.extend(ids.iter().filter_map(|id| {
 							Self::build_endpoint(
 								entity.name.clone(),
+								entity.entry_type.clone(),
 								entity_schema.clone(),
-								format!("{} single entry", entity.name.clone()),
+								format!("{} single entry ({})", entity.name.clone(), id),
 								url_single,
-								vec![("id", id)],
+								&[("id", id)],
 							)
+							.map(|mut endpoint| {
+								endpoint.check = Check::SingleEntry;
+								endpoint.integrity = Integrity {
+									single: Some((entity.id_pointer.clone(), id.to_string())),
+									parent: None,
+									references: self.references(entity),
+									sampling: *sampling,
+								};
+								endpoint
+							})
+							.map(Ok)
 						}));
 					}
 
@@ -238,14 +335,19 @@ impl Model {
 					// }
 
 					if let Some(related_endpoints) = &entity.related_endpoints {
-						endpoints.extend(related_endpoints.iter().flat_map(|(_, related_endpoint)| {
-							ids.iter().take(1).map(|id| {
+						endpoints.extend(related_endpoints.values().flat_map(|related_endpoint| {
+							ids.iter().filter_map(|id| {
 								let default_entity_name = "Unknown entity".to_string();
 								let related_entity_name = self
 									.entities_names
 									.get(&related_endpoint.returned_entry_type)
 									.unwrap_or(&default_entity_name);
-								let name = format!("{} related with a {}", related_entity_name, entity.name.clone());
+								let name = format!(
+									"{} related with a {} ({})",
+									related_entity_name,
+									entity.name.clone(),
+									id
+								);
 								let related_entity_schema = &self
 									.entities
 									.iter()
@@ -254,11 +356,18 @@ impl Model {
 									.schema;
 								Self::build_endpoint(
 									entity.name.clone(),
+									related_endpoint.returned_entry_type.clone(),
 									related_entity_schema.clone(),
 									name,
 									&related_endpoint.url,
-									vec![("id", id)],
+									&[("id", id)],
 								)
+								.map(|mut endpoint| {
+									endpoint.check = Check::RelatedEndpoint;
+									endpoint.integrity.parent = Some((Self::reference_pointer(entity), id.to_string()));
+									endpoint
+								})
+								.map(Ok)
 							})
 						}));
 					}
@@ -269,3 +378,54 @@ impl Model {
 			.collect()
 	}
 }
+
+#[cfg(test)]
+mod tests {
+
+	use std::collections::BTreeMap;
+	use std::rc::Rc;
+
+	use jsonschema::JSONSchema;
+	use serde_json::json;
+	use url::Url;
+
+	use crate::model::{Entity, Model};
+	use crate::resolver::LocalResolver;
+	use crate::source::Source;
+	use crate::Json;
+
+	#[test]
+	fn test_set_id_pointers() {
+		let entity = |entry_type: &str, name: &str| Entity {
+			entry_type: entry_type.to_string(),
+			name: name.to_string(),
+			url: format!("https://example.org/api/{}", name),
+			url_single: None,
+			schema: Rc::new(JSONSchema::compile(&json!({})).unwrap()),
+			id_pointer: String::from("/id"),
+			filtering_terms_url: None,
+			related_endpoints: None,
+		};
+		let mut model = Model {
+			entities: vec![entity("individual", "individuals"), entity("biosample", "biosamples")],
+			entities_names: BTreeMap::new(),
+			configuration_json: Json::Null,
+			beacon_map_json: Json::Null,
+			endpoints_json: Json::Null,
+			files: BTreeMap::new(),
+			source: Source::new(&Url::parse("file:///model").unwrap(), None, None),
+			resolver: LocalResolver::default(),
+			id_pointers: BTreeMap::new(),
+		};
+		model.set_id_pointers(BTreeMap::from([(
+			String::from("individual"),
+			String::from("/identifiers/0"),
+		)]));
+		assert_eq!(model.entities[0].id_pointer, "/identifiers/0");
+		assert_eq!(model.entities[1].id_pointer, "/id");
+		assert_eq!(
+			model.id_pointers.get("individual").map(String::as_str),
+			Some("/identifiers/0")
+		);
+	}
+}
@@ -0,0 +1,146 @@
+use jsonschema::JSONSchema;
+use reqwest::blocking::Client;
+use url::Url;
+
+use crate::checks::Check;
+use crate::error::VerifierError;
+use crate::model::Entity;
+use crate::output::EndpointReport;
+use crate::{utils, Json};
+
+/// Id that no beacon is expected to have
+const UNKNOWN_ID: &str = "beacon-verifier-unknown-id";
+
+/// Answers accepted for a bad request besides an error response
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Expected {
+	/// Only an error response
+	Error,
+	/// A response without results
+	ErrorOrEmpty,
+	/// Any response, the beacon may ignore what is wrong with the request
+	ErrorOrIgnored,
+}
+
+/// Sends bad requests to the endpoints of an entity and checks that the beacon rejects them with a 4xx status and
+/// an error response instead of failing (5xx, HTML pages...). `error_schema` is the error response of the framework.
+pub fn check_error_responses(
+	client: &Client,
+	root_url: &Url,
+	entity: &Entity,
+	error_schema: Option<&JSONSchema>,
+) -> Vec<EndpointReport> {
+	let main_url = match utils::expand_template(&entity.url, &[]).and_then(|url| Url::parse(&url).ok()) {
+		Some(url) => utils::url_join(root_url, &url),
+		None => return Vec::new(),
+	};
+	let with_query = |key: &str, value: &str| {
+		let mut url = main_url.clone();
+		url.query_pairs_mut().append_pair(key, value);
+		url
+	};
+
+	let mut requests = Vec::new();
+	if let Some(url_single) = &entity.url_single {
+		if let Some(url) =
+			utils::expand_template(url_single, &[("id", UNKNOWN_ID)]).and_then(|url| Url::parse(&url).ok())
+		{
+			requests.push(("unknown id", utils::url_join(root_url, &url), Expected::ErrorOrEmpty));
+		}
+	}
+	requests.push((
+		"malformed filters",
+		with_query("filters", "[{\"id\":"),
+		Expected::ErrorOrIgnored,
+	));
+	requests.push((
+		"invalid granularity",
+		with_query("requestedGranularity", "invalid"),
+		Expected::Error,
+	));
+	requests.push((
+		"unsupported parameter",
+		with_query("beaconVerifierUnsupported", "1"),
+		Expected::ErrorOrIgnored,
+	));
+
+	requests
+		.into_iter()
+		.map(|(request, url, expected)| {
+			log::info!("Validating the error response of {:?} to an {}", entity.name, request);
+			let report = EndpointReport::new(
+				&entity.name,
+				&format!("{} error response ({})", entity.name, request),
+				url.clone(),
+			);
+			match utils::request_url(client, &url) {
+				Ok((status, body)) => match check(status, body.as_ref(), expected, error_schema) {
+					Ok(()) => report.ok(None),
+					Err(e) => report.error(e),
+				},
+				Err(e) => report.null(e),
+			}
+			.check(Check::ErrorResponse)
+		})
+		.collect()
+}
+
+fn check(
+	status: u16,
+	body: Option<&Json>,
+	expected: Expected,
+	error_schema: Option<&JSONSchema>,
+) -> Result<(), VerifierError> {
+	let body =
+		body.ok_or_else(|| VerifierError::BadErrorResponse(format!("HTTP {} with a body that is not JSON", status)))?;
+	match status {
+		200..=299 if expected == Expected::Error => Err(VerifierError::BadErrorResponse(format!(
+			"accepted with HTTP {} instead of a 4xx error",
+			status
+		))),
+		200..=299 if expected == Expected::ErrorOrEmpty && body["responseSummary"]["exists"] == true => Err(
+			VerifierError::BadErrorResponse(format!("HTTP {} with results for an unknown id", status)),
+		),
+		200..=299 => Ok(()),
+		400..=499 => {
+			if let Some(error_schema) = error_schema {
+				utils::valid_schema(error_schema, body)?;
+			}
+			match body["error"]["errorCode"].as_u64() {
+				Some(code) if code != u64::from(status) => Err(VerifierError::BadErrorResponse(format!(
+					"HTTP {} with errorCode {}",
+					status, code
+				))),
+				_ => Ok(()),
+			}
+		},
+		_ => Err(VerifierError::BadErrorResponse(format!(
+			"HTTP {} instead of a 4xx error",
+			status
+		))),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+
+	use serde_json::json;
+
+	use crate::negative::{check, Expected};
+
+	#[test]
+	fn test_check() {
+		let error = json!({"meta": {}, "error": {"errorCode": 400, "errorMessage": "Bad request"}});
+		let empty = json!({"meta": {}, "responseSummary": {"exists": false}});
+		let found = json!({"meta": {}, "responseSummary": {"exists": true}});
+
+		assert!(check(400, Some(&error), Expected::Error, None).is_ok());
+		assert!(check(404, Some(&error), Expected::Error, None).is_err());
+		assert!(check(500, Some(&error), Expected::Error, None).is_err());
+		assert!(check(400, None, Expected::Error, None).is_err());
+		assert!(check(200, Some(&empty), Expected::Error, None).is_err());
+		assert!(check(200, Some(&empty), Expected::ErrorOrEmpty, None).is_ok());
+		assert!(check(200, Some(&found), Expected::ErrorOrEmpty, None).is_err());
+		assert!(check(200, Some(&found), Expected::ErrorOrIgnored, None).is_ok());
+	}
+}
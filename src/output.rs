@@ -1,10 +1,16 @@
 use std::collections::BTreeMap;
+use std::path::PathBuf;
 
 use chrono::NaiveDateTime;
 use serde::{Deserialize, Serialize};
 use url::Url;
 
+use crate::checks::{Check, CoverageStatus, RequirementCoverage, Severity, SpecReference};
 use crate::error::VerifierError;
+use crate::handovers::HandoverCoverage;
+use crate::profiles::{Profile, Requirement};
+use crate::source::Source;
+use crate::waivers::Waivers;
 use crate::Json;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -12,31 +18,220 @@ pub struct BeaconOutput {
 	pub name: String,
 	pub url: Url,
 	pub last_updated: NaiveDateTime,
+	pub model: Option<Source>,
+	pub framework: Option<Source>,
+	/// Beacon specification version claimed in the `apiVersion` of `/info`
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub spec_version: Option<String>,
+	/// Release of the specification whose framework and model were chosen for the spec version
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub release: Option<String>,
+	/// Handovers found in the responses of each entity
+	#[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+	pub handovers: BTreeMap<String, HandoverCoverage>,
+	/// Conformance profile of the beacon
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub profile: Option<Profile>,
+	/// Whether the beacon passes each conformance profile
+	#[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+	pub profiles: BTreeMap<Profile, bool>,
+	/// Requirements of the specification covered by the checks
+	#[serde(default, skip_serializing_if = "Vec::is_empty")]
+	pub requirements: Vec<RequirementCoverage>,
 	pub entities: BTreeMap<String, Vec<EndpointOutput>>,
 }
 
 impl BeaconOutput {
-	pub fn summary(&self) {
+	/// Applies the severities of the checks and the waivers to the reports that did not pass. `beacon` are the url,
+	/// name and id of the beacon.
+	pub fn judge(
+		entities: &mut BTreeMap<String, Vec<EndpointOutput>>,
+		beacon: &[&str],
+		severities: &BTreeMap<Check, Severity>,
+		waivers: &Waivers,
+	) {
+		let today = chrono::offset::Utc::now().naive_utc().date();
+		for (entity_name, reports) in entities {
+			for report in reports.iter_mut().filter(|report| report.valid != Some(true)) {
+				let check = report.check;
+				report.judge(
+					|severity| {
+						check
+							.and_then(|check| severities.get(&check).copied())
+							.or(severity)
+							.or_else(|| check.map(Check::severity))
+					},
+					|error| waivers.find(beacon, entity_name, error, today),
+				);
+			}
+		}
+	}
+}
+
+pub trait Summary {
+	fn summary(&self);
+}
+
+impl Summary for BeaconOutput {
+	fn summary(&self) {
 		self.entities.iter().for_each(|(entity_name, output)| {
-			if output.iter().all(|report| report.valid == Some(true)) {
+			let output = output
+				.iter()
+				.filter(|report| report.requirement != Some(Requirement::NotApplicable))
+				.collect::<Vec<_>>();
+			if output.iter().all(|report| report.failure() != Some(Severity::Error)) {
 				log::info!("{} \u{2713}", entity_name);
 			}
 			else {
 				log::error!("{} \u{2717}", entity_name);
-				for error in output.iter().filter_map(|report| report.error.clone()) {
-					log::error!("\t{}", error.trim());
+			}
+			for report in output.iter().filter(|report| report.valid != Some(true)) {
+				let error = report.error.as_deref().unwrap_or("").trim();
+				match (&report.waiver, report.failure()) {
+					(Some(waiver), _) => log::warn!("\t(waived: {}) {}", waiver, error),
+					(None, Some(Severity::Error)) => log::error!("\t{}", error),
+					(None, Some(Severity::Warning)) => log::warn!("\t{}", error),
+					(None, _) => log::info!("\t{}", error),
 				}
 			}
 		});
+		for (profile, passes) in &self.profiles {
+			let selected = if Some(*profile) == self.profile {
+				" (profile of the beacon)"
+			}
+			else {
+				""
+			};
+			if *passes {
+				log::info!("Profile {:?}{} \u{2713}", profile, selected);
+			}
+			else {
+				log::error!("Profile {:?}{} \u{2717}", profile, selected);
+			}
+		}
+		if !self.requirements.is_empty() {
+			let count = |status| {
+				self.requirements
+					.iter()
+					.filter(|requirement| requirement.status == status)
+					.count()
+			};
+			log::info!(
+				"Requirements: {} passed, {} failed, {} not testable",
+				count(CoverageStatus::Passed),
+				count(CoverageStatus::Failed),
+				count(CoverageStatus::NotTestable)
+			);
+		}
 	}
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LintOutput {
+	pub model: Source,
+	pub last_updated: NaiveDateTime,
+	pub findings: Vec<LintFinding>,
+}
+
+impl Summary for LintOutput {
+	fn summary(&self) {
+		if self.findings.is_empty() {
+			log::info!("Model \u{2713}");
+		}
+		else {
+			log::error!("Model \u{2717} ({} findings)", self.findings.len());
+			for finding in &self.findings {
+				log::error!("\t{:?}#{}: {}", finding.file, finding.pointer, finding.message.trim());
+			}
+		}
+	}
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LintFinding {
+	pub file: PathBuf,
+	pub pointer: String,
+	pub message: String,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct EndpointOutput {
 	pub name: String,
 	pub url: Url,
 	pub valid: Option<bool>,
 	pub error: Option<String>,
+	/// Schema claimed by the response in `meta.returnedSchemas`
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub schema: Option<String>,
+	/// Url template of the model the url was expanded from
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub template: Option<String>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub check: Option<Check>,
+	/// Requirement of the specification enforced by the check
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub reference: Option<SpecReference>,
+	/// Requirement of the check in the profile of the beacon
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub requirement: Option<Requirement>,
+	/// Severity of the failure
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub severity: Option<Severity>,
+	/// Justification of the waivers that accept the failure
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub waiver: Option<String>,
+	/// Test suite of the query
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub suite: Option<String>,
+	/// Each error of the report (one per failing record) with its own severity, joined in `error`
+	#[serde(skip)]
+	pub errors: Vec<(String, Option<Severity>)>,
+}
+
+impl EndpointOutput {
+	/// Applies the severity and the waiver of each error, so that a waived or less severe error does not hide the
+	/// others. The report is only waived if all its errors are, and its severity is the highest one of the errors
+	/// that are not waived.
+	pub fn judge(
+		&mut self,
+		severity: impl Fn(Option<Severity>) -> Option<Severity>,
+		waiver: impl Fn(&str) -> Option<String>,
+	) {
+		if self.errors.is_empty() {
+			self.severity = severity(self.severity);
+			return;
+		}
+		let judged = self
+			.errors
+			.iter()
+			.map(|(error, error_severity)| (severity(*error_severity), waiver(error)))
+			.collect::<Vec<_>>();
+		let unwaived = judged.iter().filter(|(_, waiver)| waiver.is_none());
+		if unwaived.clone().next().is_some() {
+			self.severity = unwaived.map(|(severity, _)| *severity).max().flatten();
+			self.waiver = None;
+		}
+		else {
+			self.severity = judged.iter().map(|(severity, _)| *severity).max().flatten();
+			let mut justifications = Vec::new();
+			for justification in judged.into_iter().flat_map(|(_, waiver)| waiver) {
+				if !justifications.contains(&justification) {
+					justifications.push(justification);
+				}
+			}
+			self.waiver = Some(justifications.join("; "));
+		}
+	}
+
+	/// Severity of the failure of the report, if it did not pass and it is not waived
+	pub fn failure(&self) -> Option<Severity> {
+		if self.valid == Some(true) || self.waiver.is_some() {
+			None
+		}
+		else {
+			Some(self.severity.unwrap_or(Severity::Error))
+		}
+	}
 }
 
 pub struct Output {
@@ -51,27 +246,37 @@ impl Output {
 	}
 
 	pub fn push(&mut self, report: EndpointReport) {
-		match self.results.get_mut(&report.entity_name) {
-			Some(endpoints) => {
-				endpoints.push(EndpointOutput {
-					name: report.name,
-					url: report.url.unwrap(),
-					valid: report.valid,
-					error: report.error.map(|e| e.to_string()),
-				});
-			},
-			None => {
-				self.results.insert(
-					report.entity_name,
-					vec![EndpointOutput {
-						name: report.name,
-						url: report.url.unwrap(),
-						valid: report.valid,
-						error: report.error.map(|e| e.to_string()),
-					}],
-				);
-			},
-		}
+		let errors = report
+			.error
+			.iter()
+			.chain(&report.other_errors)
+			.map(|e| (e.to_string(), e.severity()))
+			.collect::<Vec<_>>();
+		let error = errors.iter().fold(None, |joined: Option<String>, (error, _)| {
+			Some(match joined {
+				Some(joined) if joined.ends_with('\n') => joined + error,
+				Some(joined) => joined + "\n" + error,
+				None => error.clone(),
+			})
+		});
+		self.results
+			.entry(report.entity_name)
+			.or_default()
+			.push(EndpointOutput {
+				name: report.name,
+				url: report.url.unwrap(),
+				valid: report.valid,
+				severity: errors.iter().map(|(_, severity)| *severity).max().flatten(),
+				error,
+				errors,
+				schema: report.schema,
+				template: report.template,
+				check: report.check,
+				reference: report.check.map(Check::reference),
+				requirement: None,
+				waiver: None,
+				suite: report.suite,
+			});
 	}
 
 	pub fn finish(self) -> BTreeMap<String, Vec<EndpointOutput>> {
@@ -91,9 +296,15 @@ pub struct EndpointReport {
 	pub entity_name: String,
 	pub valid: Option<bool>,
 	pub error: Option<VerifierError>,
+	/// Errors of the other failing records, when several records of a response fail
+	pub other_errors: Vec<VerifierError>,
 	pub output: Option<Json>,
 	pub url: Option<Url>,
 	pub name: String,
+	pub schema: Option<String>,
+	pub template: Option<String>,
+	pub check: Option<Check>,
+	pub suite: Option<String>,
 }
 
 impl EndpointReport {
@@ -129,10 +340,35 @@ impl EndpointReport {
 		self
 	}
 
-	pub fn join(self, report2: Self) -> Self {
+	pub fn schema(mut self, schema: Option<String>) -> Self {
+		self.schema = schema;
+		self
+	}
+
+	pub fn template(mut self, template: Option<String>) -> Self {
+		self.template = template;
+		self
+	}
+
+	pub fn check(mut self, check: Check) -> Self {
+		self.check = Some(check);
+		self
+	}
+
+	pub fn suite(mut self, suite: &str) -> Self {
+		self.suite = Some(suite.to_string());
+		self
+	}
+
+	/// Joins the reports of two records, keeping the errors of both if they fail
+	pub fn join(mut self, mut report2: Self) -> Self {
 		if self.valid == Some(true) && (report2.valid.is_none() || !report2.valid.unwrap()) {
 			return report2;
 		};
+		if self.valid != Some(true) && report2.valid != Some(true) {
+			self.other_errors.extend(report2.error.take());
+			self.other_errors.append(&mut report2.other_errors);
+		}
 		self
 	}
 }
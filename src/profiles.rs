@@ -0,0 +1,75 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::checks::{Check, Severity};
+use crate::output::EndpointOutput;
+use crate::Json;
+
+/// Conformance profile of a beacon, after the most detailed granularity it is meant to return
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Profile {
+	Boolean,
+	Count,
+	Aggregated,
+	Record,
+}
+
+/// Whether a check has to pass to conform to a profile
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Requirement {
+	Required,
+	Optional,
+	NotApplicable,
+}
+
+impl Profile {
+	pub const ALL: [Self; 4] = [Self::Boolean, Self::Count, Self::Aggregated, Self::Record];
+
+	/// Profile of the `securityAttributes.defaultGranularity` of `/configuration`
+	pub fn from_configuration(configuration: &Json) -> Option<Self> {
+		serde_json::from_value(configuration["response"]["securityAttributes"]["defaultGranularity"].clone()).ok()
+	}
+
+	pub fn requirement(self, check: Check) -> Requirement {
+		check.requirement(self)
+	}
+
+	/// Whether all the checks required by the profile passed, were waived or failed with a severity lower than error
+	pub fn passes(self, entities: &BTreeMap<String, Vec<EndpointOutput>>) -> bool {
+		entities
+			.values()
+			.flatten()
+			.filter(|report| report.check.map(|check| self.requirement(check)) == Some(Requirement::Required))
+			.all(|report| report.failure() != Some(Severity::Error))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+
+	use serde_json::json;
+
+	use crate::checks::Check;
+	use crate::profiles::{Profile, Requirement};
+
+	#[test]
+	fn test_profile() {
+		let configuration = json!({"response": {"securityAttributes": {"defaultGranularity": "count"}}});
+		assert_eq!(Profile::from_configuration(&configuration), Some(Profile::Count));
+		assert_eq!(Profile::from_configuration(&json!({"response": {}})), None);
+
+		assert_eq!(
+			Profile::Count.requirement(Check::SingleEntry),
+			Requirement::NotApplicable
+		);
+		assert_eq!(Profile::Record.requirement(Check::SingleEntry), Requirement::Required);
+		assert_eq!(
+			Profile::Boolean.requirement(Check::Granularities),
+			Requirement::Optional
+		);
+		assert_eq!(Profile::Boolean.requirement(Check::Endpoint), Requirement::Required);
+	}
+}
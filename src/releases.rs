@@ -0,0 +1,117 @@
+use std::fs::File;
+use std::path::{Path, PathBuf};
+
+use reqwest::blocking::Client;
+use semver::{Version, VersionReq};
+use serde::Deserialize;
+use url::Url;
+
+use crate::consistency::BeaconIdentity;
+use crate::error::VerifierError;
+use crate::utils;
+
+/// Framework and model of a release of the Beacon specification
+#[derive(Debug, Clone, Deserialize)]
+pub struct Release {
+	pub release: String,
+	/// Spec versions the release is used for, as a semver requirement (e.g. `~2.0`)
+	pub versions: String,
+	pub framework: Url,
+	#[serde(default)]
+	pub framework_path: Option<PathBuf>,
+	pub model: Url,
+	#[serde(default)]
+	pub model_path: Option<PathBuf>,
+}
+
+/// Releases of the specification published in `ga4gh-beacon/beacon-v2`, used unless `--releases` is given
+const RELEASES: &str = include_str!("../assets/releases.json");
+
+/// Releases the verifier can choose from, in order of preference
+#[derive(Debug, Clone, Default)]
+pub struct Releases(Vec<Release>);
+
+impl Releases {
+	pub fn load(path: &Path) -> Result<Self, VerifierError> {
+		let file = File::open(path).map_err(|e| VerifierError::BadReleases(path.to_path_buf(), e.to_string()))?;
+		let releases: Vec<Release> =
+			serde_json::from_reader(file).map_err(|e| VerifierError::BadReleases(path.to_path_buf(), e.to_string()))?;
+		Self::new(releases).map_err(|e| VerifierError::BadReleases(path.to_path_buf(), e))
+	}
+
+	/// Releases embedded in the verifier
+	pub fn embedded() -> Self {
+		serde_json::from_str(RELEASES)
+			.map_err(|e| e.to_string())
+			.and_then(Self::new)
+			.expect("The embedded releases are not valid")
+	}
+
+	fn new(releases: Vec<Release>) -> Result<Self, String> {
+		for release in &releases {
+			VersionReq::parse(&release.versions).map_err(|e| format!("{}: {}", release.versions, e))?;
+		}
+		Ok(Self(releases))
+	}
+
+	/// First release whose versions match the spec version
+	pub fn find(&self, version: &Version) -> Option<&Release> {
+		self.0
+			.iter()
+			.find(|release| VersionReq::parse(&release.versions).map_or(false, |versions| versions.matches(version)))
+	}
+}
+
+/// Spec version claimed in the `apiVersion` of the `/info` of a beacon
+pub fn claimed_version(client: &Client, url: &Url) -> Result<Option<Version>, VerifierError> {
+	let mut info_url = url.clone();
+	info_url.set_path(Path::new(url.path()).join("info").to_str().unwrap_or(""));
+	let info = utils::ping_url(client, &info_url)?;
+	BeaconIdentity::new(&info).spec_version()
+}
+
+/// Parses a spec version given in the command line, with or without the `v` prefix
+pub fn parse_version(value: &str) -> Result<Version, String> {
+	Version::parse(value.trim_start_matches('v')).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+
+	use std::path::Path;
+
+	use semver::Version;
+	use url::Url;
+
+	use crate::releases::{Release, Releases};
+
+	#[test]
+	fn test_find() {
+		let release = |release: &str, versions: &str| Release {
+			release: release.into(),
+			versions: versions.into(),
+			framework: Url::parse("https://github.com/ga4gh-beacon/beacon-v2").unwrap(),
+			framework_path: None,
+			model: Url::parse("https://github.com/ga4gh-beacon/beacon-v2").unwrap(),
+			model_path: None,
+		};
+		let releases = Releases(vec![release("v2.0", "~2.0"), release("v2", "^2.0")]);
+
+		assert_eq!(releases.find(&Version::new(2, 0, 1)).unwrap().release, "v2.0");
+		assert_eq!(releases.find(&Version::new(2, 1, 0)).unwrap().release, "v2");
+		assert!(releases.find(&Version::new(1, 0, 0)).is_none());
+	}
+
+	#[test]
+	fn test_embedded() {
+		let releases = Releases::embedded();
+		let release = releases.find(&Version::new(2, 0, 0)).unwrap();
+		assert_eq!(release.release, "v2.0.0");
+		assert_eq!(
+			release.model_path.as_deref(),
+			Some(Path::new("models/json/beacon-v2-default-model"))
+		);
+		// Drafts use the default framework and model
+		assert!(releases.find(&Version::parse("2.0.0-draft.4").unwrap()).is_none());
+	}
+}
@@ -0,0 +1,224 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use jsonschema::{SchemaResolver, SchemaResolverError};
+use url::Url;
+
+use crate::error::VerifierError;
+use crate::Json;
+
+/// Resolves the `$ref`s of the schemas with the files of the framework and the model instead of downloading them.
+///
+/// Local files are identified by `file:///<source>/<relative path>` (`source` being `framework` or `model`), and
+/// remote urls are mapped to the local file with the same `$id` or, otherwise, the longest common path suffix. The
+/// suffix must have at least the directory and the name of the file, unless a single local file has that name.
+#[derive(Debug, Clone, Default)]
+pub struct LocalResolver {
+	/// Documents by `<source>/<relative path>`
+	files: BTreeMap<String, Arc<Json>>,
+	/// Key of the documents by `$id`
+	ids: BTreeMap<String, String>,
+	/// Path segments of the location of each source, used to choose between files with the same name
+	hints: BTreeMap<String, Vec<String>>,
+}
+
+impl LocalResolver {
+	/// Url used as the base of the relative `$ref`s of a local file
+	pub fn base_url(source: &str, rel_path: &Path) -> Url {
+		let mut url = Url::parse("file:///").unwrap();
+		url.path_segments_mut().unwrap().push(source).extend(
+			rel_path
+				.components()
+				.map(|c| c.as_os_str().to_string_lossy().to_string()),
+		);
+		url
+	}
+
+	/// Sets the base url of a schema unless it already has an `$id`
+	pub fn with_base(schema: &Json, url: &Url) -> Json {
+		let mut schema = schema.clone();
+		if let Some(object) = schema.as_object_mut() {
+			object.entry("$id").or_insert_with(|| Json::String(url.to_string()));
+		}
+		schema
+	}
+
+	pub fn add_files(&mut self, source: &str, location: &Url, base_path: &Path, files: &BTreeMap<PathBuf, Json>) {
+		self.hints.insert(
+			source.to_string(),
+			location
+				.path_segments()
+				.map(|segments| segments.map(String::from).collect())
+				.unwrap_or_default(),
+		);
+
+		for (path, json) in files {
+			let rel_path = path.strip_prefix(base_path).unwrap_or(path);
+			let key = Self::base_url(source, rel_path).path()[1..].to_string();
+			if let Some(id) = json.get("$id").and_then(Json::as_str) {
+				if let Ok(mut id) = Url::parse(id) {
+					id.set_fragment(None);
+					self.ids.insert(id.to_string(), key.clone());
+				}
+			}
+			self.files.insert(key, Arc::new(json.clone()));
+		}
+	}
+
+	/// Local file of a remote url, `None` if no local file matches it unambiguously
+	pub fn find(&self, url: &Url) -> Option<Arc<Json>> {
+		let mut url = url.clone();
+		url.set_fragment(None);
+
+		if url.scheme() == "file" {
+			return self.files.get(&url.path()[1..]).cloned();
+		}
+		if let Some(key) = self.ids.get(url.as_str()) {
+			return self.files.get(key).cloned();
+		}
+
+		let url_segments = url.path_segments().map(Iterator::collect::<Vec<_>>).unwrap_or_default();
+		let candidates = self
+			.files
+			.iter()
+			.map(|(key, json)| {
+				let mut key_segments = key.split('/');
+				let source = key_segments.next().unwrap_or("");
+				let suffix = key_segments
+					.rev()
+					.zip(url_segments.iter().rev())
+					.take_while(|(a, b)| a == *b)
+					.count();
+				let hint = self.hints.get(source).map_or(0, |hints| {
+					hints
+						.iter()
+						.filter(|hint| url_segments.contains(&hint.as_str()))
+						.count()
+				});
+				((suffix, hint), json)
+			})
+			.filter(|((suffix, _), _)| *suffix > 0)
+			.collect::<Vec<_>>();
+		let ((suffix, _), json) = candidates.iter().max_by_key(|(score, _)| *score)?;
+		if *suffix < 2 && candidates.len() > 1 {
+			log::debug!("{} only matches the name of several local files", url);
+			return None;
+		}
+		Some((*json).clone())
+	}
+}
+
+impl SchemaResolver for LocalResolver {
+	fn resolve(
+		&self,
+		_root_schema: &Json,
+		url: &Url,
+		original_reference: &str,
+	) -> Result<Arc<Json>, SchemaResolverError> {
+		self.find(url).ok_or_else(|| {
+			let e = VerifierError::UnresolvedReference(url.clone(), original_reference.to_string());
+			log::error!("{}", e);
+			e.into()
+		})
+	}
+}
+
+#[cfg(test)]
+mod tests {
+
+	use std::collections::BTreeMap;
+	use std::path::{Path, PathBuf};
+
+	use serde_json::json;
+	use url::Url;
+
+	use crate::resolver::LocalResolver;
+
+	#[test]
+	fn test_find() {
+		let base_path = Path::new("/tmp/model");
+		let mut files = BTreeMap::new();
+		files.insert(
+			PathBuf::from("/tmp/model/common/ontologyTerm.json"),
+			json!({"title": "model"}),
+		);
+		files.insert(
+			PathBuf::from("/tmp/model/schemas/individuals.json"),
+			json!({"$id": "https://example.org/individuals.json"}),
+		);
+		let mut framework_files = BTreeMap::new();
+		framework_files.insert(
+			PathBuf::from("/tmp/fw/common/ontologyTerm.json"),
+			json!({"title": "framework"}),
+		);
+
+		let mut resolver = LocalResolver::default();
+		resolver.add_files(
+			"model",
+			&Url::parse("https://github.com/org/models/v2").unwrap(),
+			base_path,
+			&files,
+		);
+		resolver.add_files(
+			"framework",
+			&Url::parse("https://github.com/org/framework").unwrap(),
+			Path::new("/tmp/fw"),
+			&framework_files,
+		);
+
+		let find = |url: &str| resolver.find(&Url::parse(url).unwrap()).map(|json| (*json).clone());
+		assert_eq!(
+			find("file:///model/common/ontologyTerm.json#/x"),
+			Some(json!({"title": "model"}))
+		);
+		assert_eq!(
+			find("https://raw.githubusercontent.com/org/framework/main/common/ontologyTerm.json"),
+			Some(json!({"title": "framework"}))
+		);
+		assert_eq!(
+			find("https://raw.githubusercontent.com/org/models/main/v2/common/ontologyTerm.json"),
+			Some(json!({"title": "model"}))
+		);
+		assert_eq!(
+			find("https://example.org/individuals.json#/properties"),
+			Some(json!({"$id": "https://example.org/individuals.json"}))
+		);
+		assert_eq!(find("https://example.org/unknown.json"), None);
+	}
+
+	#[test]
+	fn test_find_only_file_name() {
+		let mut files = BTreeMap::new();
+		files.insert(
+			PathBuf::from("/tmp/fw/common/ontologyTerm.json"),
+			json!({"title": "common"}),
+		);
+		files.insert(
+			PathBuf::from("/tmp/fw/configuration/ontologyTerm.json"),
+			json!({"title": "configuration"}),
+		);
+		files.insert(
+			PathBuf::from("/tmp/fw/responses/beaconInfoResponse.json"),
+			json!({"title": "info"}),
+		);
+		let mut resolver = LocalResolver::default();
+		resolver.add_files(
+			"framework",
+			&Url::parse("https://github.com/org/framework").unwrap(),
+			Path::new("/tmp/fw"),
+			&files,
+		);
+
+		let find = |url: &str| resolver.find(&Url::parse(url).unwrap()).map(|json| (*json).clone());
+		assert_eq!(find("https://example.org/other-spec/ontologyTerm.json"), None);
+		assert_eq!(
+			find("https://example.org/other-spec/configuration/ontologyTerm.json"),
+			Some(json!({"title": "configuration"}))
+		);
+		assert_eq!(
+			find("https://example.org/other-spec/beaconInfoResponse.json"),
+			Some(json!({"title": "info"}))
+		);
+	}
+}
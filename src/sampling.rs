@@ -0,0 +1,60 @@
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+
+/// Ids of the main endpoint of an entity used to build the `{id}` urls
+#[derive(Debug, Clone, Copy)]
+pub enum Sampling {
+	/// The first `n` ids
+	First(usize),
+	/// `n` ids at random, the same seed always picks the same ids
+	Random {
+		size: usize,
+		seed: u64,
+	},
+	All,
+}
+
+/// The first id, like the command line
+impl Default for Sampling {
+	fn default() -> Self {
+		Self::First(1)
+	}
+}
+
+impl Sampling {
+	/// Sampled ids, in the order in which they were returned
+	pub fn sample<'a>(&self, ids: &'a [String]) -> Vec<&'a String> {
+		match *self {
+			Self::First(size) => ids.iter().take(size).collect(),
+			Self::Random { size, seed } => {
+				let mut rng = ChaCha8Rng::seed_from_u64(seed);
+				let mut indexes = rand::seq::index::sample(&mut rng, ids.len(), size.min(ids.len())).into_vec();
+				indexes.sort_unstable();
+				indexes.into_iter().map(|i| &ids[i]).collect()
+			},
+			Self::All => ids.iter().collect(),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+
+	use crate::sampling::Sampling;
+
+	#[test]
+	fn test_sample() {
+		let ids = (0..10).map(|i| format!("id{}", i)).collect::<Vec<_>>();
+
+		assert_eq!(Sampling::First(2).sample(&ids), vec!["id0", "id1"]);
+		assert_eq!(Sampling::First(20).sample(&ids).len(), 10);
+		assert_eq!(Sampling::All.sample(&ids).len(), 10);
+
+		let random = Sampling::Random { size: 3, seed: 42 };
+		let sampled = random.sample(&ids);
+		assert_eq!(sampled.len(), 3);
+		assert_eq!(sampled, random.sample(&ids));
+		assert!(sampled.windows(2).all(|pair| pair[0] < pair[1]));
+		assert_eq!(Sampling::Random { size: 3, seed: 42 }.sample(&[]).len(), 0);
+	}
+}
@@ -0,0 +1,264 @@
+use std::collections::BTreeMap;
+use std::rc::Rc;
+
+use jsonschema::JSONSchema;
+use reqwest::blocking::Client;
+use url::Url;
+
+use crate::error::VerifierError;
+use crate::interface::{BeaconEntryType, ReferenceToAnSchema, ReturnedSchema};
+use crate::resolver::LocalResolver;
+use crate::{utils, Json};
+
+/// Schemas advertised by a beacon in the `defaultSchema` and `additionallySupportedSchemas` of the entry types of
+/// `/entry_types` and `/configuration`
+pub struct BeaconSchemas {
+	/// Advertised entry types by id
+	entry_types: BTreeMap<String, BeaconEntryType>,
+	/// Schemas by entry type and schema id
+	schemas: BTreeMap<(String, String), AdvertisedSchema>,
+}
+
+struct AdvertisedSchema {
+	reference: ReferenceToAnSchema,
+	/// Compiled schema and location of the ids of its records, or the reason it could not be loaded
+	schema: Result<(Rc<JSONSchema>, String), String>,
+}
+
+/// Schema claimed by a response in `meta.returnedSchemas`
+pub struct ClaimedSchema {
+	pub name: String,
+	pub schema: Rc<JSONSchema>,
+	pub id_pointer: String,
+}
+
+impl BeaconSchemas {
+	/// `responses` are the responses of `/entry_types` and `/configuration`. Advertised schemas are looked up in the
+	/// files of the resolver and only downloaded if they are not there, unless there is no `client` (offline).
+	pub fn load(client: Option<&Client>, responses: &[Json], base_url: &Url, resolver: &LocalResolver) -> Self {
+		let mut entry_types = BTreeMap::new();
+		let mut schemas = BTreeMap::new();
+		for response in responses {
+			let advertised = response["response"]["entryTypes"]
+				.as_object()
+				.cloned()
+				.unwrap_or_default();
+			for (key, entry_type) in advertised {
+				let entry_type: BeaconEntryType = match serde_json::from_value(entry_type) {
+					Ok(entry_type) => entry_type,
+					Err(e) => {
+						log::warn!("Skipping advertised entry type {}: {}", key, e);
+						continue;
+					},
+				};
+				for reference in std::iter::once(&entry_type.default_schema)
+					.chain(&entry_type.additionally_supported_schemas)
+					.cloned()
+				{
+					let key = (entry_type.id.clone(), reference.id.clone());
+					if schemas.contains_key(&key) {
+						continue;
+					}
+					log::debug!("Loading schema {} advertised for {}", reference.id, entry_type.id);
+					let schema = Self::compile(client, &reference, base_url, resolver).map_err(|e| {
+						log::error!("Schema {} could not be loaded: {}", reference.id, e);
+						e
+					});
+					schemas.insert(key, AdvertisedSchema { reference, schema });
+				}
+				entry_types.entry(entry_type.id.clone()).or_insert(entry_type);
+			}
+		}
+		Self { entry_types, schemas }
+	}
+
+	fn compile(
+		client: Option<&Client>,
+		reference: &ReferenceToAnSchema,
+		base_url: &Url,
+		resolver: &LocalResolver,
+	) -> Result<(Rc<JSONSchema>, String), String> {
+		let url = base_url
+			.join(&reference.reference_to_schema_definition)
+			.map_err(|e| e.to_string())?;
+		let schema = match (resolver.find(&url), client) {
+			(Some(schema), _) => (*schema).clone(),
+			(None, Some(client)) => utils::ping_url(client, &url).map_err(|e| e.to_string())?,
+			(None, None) => {
+				return Err(format!(
+					"{} is not in the framework or the model and it cannot be downloaded offline",
+					url
+				))
+			},
+		};
+		JSONSchema::options()
+			.with_meta_schemas()
+			.with_resolver(resolver.clone())
+			.compile(&LocalResolver::with_base(&schema, &url))
+			.map(|compiled| (Rc::new(compiled), utils::default_id_pointer(&schema)))
+			.map_err(|e| e.to_string())
+	}
+
+	/// Schema claimed for an entry type, `None` if the response does not claim any
+	pub fn claimed(
+		&self,
+		entry_type: &str,
+		returned_schemas: &[ReturnedSchema],
+	) -> Result<Option<ClaimedSchema>, VerifierError> {
+		let returned = match returned_schemas
+			.iter()
+			.find(|returned| returned.entity_type.eq_ignore_ascii_case(entry_type))
+		{
+			Some(returned) => returned,
+			None => return Ok(None),
+		};
+
+		self.schemas
+			.iter()
+			.find(|((advertised_entry_type, _), advertised)| {
+				advertised_entry_type.eq_ignore_ascii_case(entry_type)
+					&& (advertised.reference.id == returned.schema
+						|| advertised.reference.reference_to_schema_definition == returned.schema)
+			})
+			.map(|(_, advertised)| advertised)
+			.ok_or_else(|| VerifierError::UnadvertisedSchema(returned.schema.clone(), entry_type.to_string()))?
+			.claimed()
+			.map(Some)
+	}
+
+	/// Entry type advertised by the beacon
+	pub fn entry_type(&self, entry_type: &str) -> Option<&BeaconEntryType> {
+		self.entry_types.get(entry_type)
+	}
+
+	/// Default schema advertised for an entry type
+	pub fn default_schema(&self, entry_type: &str) -> Result<ClaimedSchema, VerifierError> {
+		let advertised = self
+			.entry_types
+			.get(entry_type)
+			.and_then(|advertised| {
+				self.schemas
+					.get(&(advertised.id.clone(), advertised.default_schema.id.clone()))
+			})
+			.ok_or_else(|| VerifierError::UnadvertisedEntryType(entry_type.to_string()))?;
+		advertised.claimed()
+	}
+}
+
+impl AdvertisedSchema {
+	fn claimed(&self) -> Result<ClaimedSchema, VerifierError> {
+		match &self.schema {
+			Ok((schema, id_pointer)) => Ok(ClaimedSchema {
+				name: self.reference.schema_version.as_ref().map_or_else(
+					|| self.reference.id.clone(),
+					|version| format!("{} ({})", self.reference.id, version),
+				),
+				schema: schema.clone(),
+				id_pointer: id_pointer.clone(),
+			}),
+			Err(e) => Err(VerifierError::UnavailableSchema(self.reference.id.clone(), e.clone())),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+
+	use std::collections::BTreeMap;
+	use std::path::{Path, PathBuf};
+
+	use serde_json::json;
+	use url::Url;
+
+	use crate::interface::ReturnedSchema;
+	use crate::resolver::LocalResolver;
+	use crate::schemas::BeaconSchemas;
+
+	/// Schemas advertised for individuals, with only the default one in the model
+	fn schemas() -> BeaconSchemas {
+		let mut files = BTreeMap::new();
+		files.insert(
+			PathBuf::from("/tmp/model/individuals/defaultSchema.json"),
+			json!({
+				"$id": "https://example.org/schemas/individuals/defaultSchema.json",
+				"required": ["id"],
+				"properties": {"id": {"type": "string"}}
+			}),
+		);
+		let mut resolver = LocalResolver::default();
+		resolver.add_files(
+			"model",
+			&Url::parse("file:///tmp/model").unwrap(),
+			Path::new("/tmp/model"),
+			&files,
+		);
+		let entry_types = json!({"response": {"entryTypes": {"individual": {
+			"id": "individual",
+			"defaultSchema": {
+				"id": "beacon-v2-individual",
+				"referenceToSchemaDefinition": "https://example.org/schemas/individuals/defaultSchema.json",
+				"schemaVersion": "v2.0.0"
+			},
+			"additionallySupportedSchemas": [{
+				"id": "phenopacket",
+				"referenceToSchemaDefinition": "https://example.org/schemas/phenopacket.json"
+			}]
+		}}}});
+		BeaconSchemas::load(
+			None,
+			&[entry_types],
+			&Url::parse("https://example.org/api").unwrap(),
+			&resolver,
+		)
+	}
+
+	fn returned(schema: &str) -> Vec<ReturnedSchema> {
+		vec![ReturnedSchema {
+			entity_type: String::from("individual"),
+			schema: schema.to_string(),
+		}]
+	}
+
+	#[test]
+	fn test_claimed_local() {
+		let schemas = schemas();
+		let claimed = schemas
+			.claimed("individual", &returned("beacon-v2-individual"))
+			.unwrap()
+			.unwrap();
+		assert_eq!(claimed.name, "beacon-v2-individual (v2.0.0)");
+		assert_eq!(claimed.id_pointer, "/id");
+		assert!(claimed.schema.is_valid(&json!({"id": "ind1"})));
+		assert!(!claimed.schema.is_valid(&json!({"id": 1})));
+
+		assert_eq!(
+			schemas.default_schema("individual").unwrap().name,
+			"beacon-v2-individual (v2.0.0)"
+		);
+		assert!(schemas
+			.claimed("biosample", &returned("beacon-v2-individual"))
+			.unwrap()
+			.is_none());
+	}
+
+	#[test]
+	fn test_claimed_unknown() {
+		let schemas = schemas();
+		assert_eq!(
+			schemas
+				.claimed("individual", &returned("beacon-v2-biosample"))
+				.err()
+				.unwrap()
+				.to_string(),
+			"The response claims schema beacon-v2-biosample for individual, which is not advertised in /entry_types \
+			 or /configuration"
+		);
+		// Advertised, but not in the model and not downloaded offline
+		assert_eq!(
+			schemas.claimed("individual", &returned("phenopacket")).err().unwrap().to_string(),
+			"Schema phenopacket advertised by the beacon could not be loaded: \
+			 https://example.org/schemas/phenopacket.json is not in the framework or the model and it cannot be \
+			 downloaded offline"
+		);
+	}
+}
@@ -0,0 +1,99 @@
+use crate::error::VerifierError;
+use crate::Json;
+
+/// Schema of the GA4GH service-info of a beacon (`type` `org.ga4gh:beacon`)
+pub const SCHEMA: &str = include_str!("../assets/service-info.json");
+
+/// Checks that the `id`, `name`, `version` and `organization` of `/service-info` are the ones of `/info`
+pub fn check(service_info: &Json, info: &Json) -> Result<(), VerifierError> {
+	let info = &info["response"];
+	let mut errors = Vec::new();
+	for (field, service_info_value, info_value) in [
+		("id", &service_info["id"], &info["id"]),
+		("name", &service_info["name"], &info["name"]),
+		("version", &service_info["version"], &info["version"]),
+		(
+			"organization.name",
+			&service_info["organization"]["name"],
+			&info["organization"]["name"],
+		),
+		(
+			"organization.url",
+			&service_info["organization"]["url"],
+			&info["organization"]["welcomeUrl"],
+		),
+	] {
+		if let (Some(service_info_value), Some(info_value)) = (service_info_value.as_str(), info_value.as_str()) {
+			if service_info_value.trim_end_matches('/') != info_value.trim_end_matches('/') {
+				errors.push(format!(
+					"{} is {} but it is {} in /info",
+					field, service_info_value, info_value
+				));
+			}
+		}
+	}
+
+	if errors.is_empty() {
+		Ok(())
+	}
+	else {
+		Err(VerifierError::ServiceInfoMismatch(errors.join("\n")))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+
+	use serde_json::json;
+
+	use crate::service_info::check;
+
+	#[test]
+	fn test_check() {
+		let info = json!({"response": {
+			"id": "org.example.beacon",
+			"name": "Beacon",
+			"version": "v1.0",
+			"organization": {"id": "org", "name": "Org", "welcomeUrl": "https://example.org/"}
+		}});
+		let service_info = |name| {
+			json!({
+				"id": "org.example.beacon",
+				"name": name,
+				"type": {"group": "org.ga4gh", "artifact": "beacon", "version": "v2.0.0"},
+				"organization": {"name": "Org", "url": "https://example.org"},
+				"version": "v1.0"
+			})
+		};
+
+		assert!(check(&service_info("Beacon"), &info).is_ok());
+		assert_eq!(
+			check(&service_info("Other beacon"), &info).unwrap_err().to_string(),
+			"/service-info does not match /info: name is Other beacon but it is Beacon in /info"
+		);
+		// Missing fields are reported by the schema validation
+		assert!(check(
+			&service_info("Beacon"),
+			&json!({"response": {"id": "org.example.beacon"}})
+		)
+		.is_ok());
+	}
+
+	#[test]
+	fn test_check_organization() {
+		let info = json!({"response": {
+			"id": "org.example.beacon",
+			"organization": {"id": "org", "name": "Org", "welcomeUrl": "https://example.org/"}
+		}});
+		let service_info = json!({
+			"id": "org.example.beacon",
+			"organization": {"name": "Other org", "url": "https://example.com"}
+		});
+
+		assert_eq!(
+			check(&service_info, &info).unwrap_err().to_string(),
+			"/service-info does not match /info: organization.name is Other org but it is Org in /info\norganization.url \
+			 is https://example.com but it is https://example.org/ in /info"
+		);
+	}
+}
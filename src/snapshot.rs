@@ -0,0 +1,46 @@
+use url::Url;
+
+mod embedded {
+	include!(concat!(env!("OUT_DIR"), "/snapshots.rs"));
+}
+
+/// Snapshots of the default framework and model embedded in the binary (`embedded://framework` and
+/// `embedded://model`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Snapshot {
+	Framework,
+	Model,
+}
+
+impl Snapshot {
+	pub fn from_location(location: &Url) -> Option<Self> {
+		match (location.scheme(), location.host_str()) {
+			("embedded", Some("framework")) => Some(Self::Framework),
+			("embedded", Some("model")) => Some(Self::Model),
+			_ => None,
+		}
+	}
+
+	pub fn location(self) -> Url {
+		match self {
+			Self::Framework => Url::parse("embedded://framework").unwrap(),
+			Self::Model => Url::parse("embedded://model").unwrap(),
+		}
+	}
+
+	/// `.tar.gz` archive of the snapshot, `None` if it was not embedded in this build
+	pub fn archive(self) -> Option<&'static [u8]> {
+		match self {
+			Self::Framework => embedded::FRAMEWORK_ARCHIVE,
+			Self::Model => embedded::MODEL_ARCHIVE,
+		}
+	}
+
+	/// Git commit the snapshot was taken from
+	pub fn version(self) -> Option<&'static str> {
+		match self {
+			Self::Framework => embedded::FRAMEWORK_VERSION,
+			Self::Model => embedded::MODEL_VERSION,
+		}
+	}
+}
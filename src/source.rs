@@ -0,0 +1,457 @@
+use std::path::{Path, PathBuf};
+
+use git2::build::{CheckoutBuilder, RepoBuilder};
+use git2::{Cred, CredentialType, FetchOptions, Object, ObjectType, RemoteCallbacks, Repository};
+use reqwest::blocking::Client;
+use serde::{Deserialize, Serialize};
+use tempfile::TempDir;
+use url::Url;
+
+use crate::cache::Cache;
+use crate::error::VerifierError;
+use crate::snapshot::Snapshot;
+use crate::utils;
+
+/// Where a model or a framework comes from
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Source {
+	pub location: Url,
+	/// Subdirectory inside the git repository
+	pub path: Option<PathBuf>,
+	/// Git branch, tag or commit requested
+	pub reference: Option<String>,
+	/// Git commit that was resolved from the reference (or the one of the embedded snapshot)
+	pub commit: Option<String>,
+	/// Kind of location that was used, once fetched
+	pub kind: Option<SourceKind>,
+	#[serde(skip)]
+	cache: Option<Cache>,
+	#[serde(skip)]
+	fallback: Option<Snapshot>,
+	#[serde(skip)]
+	client: Option<Client>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SourceKind {
+	Directory,
+	Git,
+	Archive,
+	Embedded,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ArchiveFormat {
+	TarGz,
+	Zip,
+}
+
+impl ArchiveFormat {
+	fn from_location(location: &Url) -> Option<Self> {
+		let path = location.path();
+		if path.ends_with(".tar.gz") || path.ends_with(".tgz") {
+			Some(Self::TarGz)
+		}
+		else if path.ends_with(".zip") {
+			Some(Self::Zip)
+		}
+		else {
+			None
+		}
+	}
+}
+
+impl Source {
+	/// The reference can be given explicitly or inside the location (`https://github.com/<owner>/<repo>@<ref>/<path>`).
+	/// The explicit reference has precedence.
+	pub fn new(location: &Url, reference: Option<String>, path: Option<PathBuf>) -> Self {
+		let mut location = location.clone();
+		let mut url_reference = None;
+
+		let segments = Some(&location)
+			.filter(|location| location.scheme() != "file")
+			.and_then(Url::path_segments)
+			.map(|segments| segments.map(String::from).collect::<Vec<_>>())
+			.unwrap_or_default();
+		if let Some(i) = segments.iter().position(|segment| segment.contains('@')) {
+			let mut segments = segments;
+			let (name, segment_reference) = segments[i].split_once('@').unwrap();
+			url_reference = Some(segment_reference.to_string());
+			segments[i] = name.to_string();
+			location.set_path(&segments.join("/"));
+		}
+
+		Self {
+			location,
+			path,
+			reference: reference.or(url_reference),
+			commit: None,
+			kind: None,
+			cache: None,
+			fallback: None,
+			client: None,
+		}
+	}
+
+	/// Keeps the cloned repository in the cache instead of a temporary directory
+	pub fn cache(mut self, cache: Option<Cache>) -> Self {
+		self.cache = cache;
+		self
+	}
+
+	/// Falls back to an embedded snapshot when the location cannot be fetched
+	pub fn fallback(mut self, snapshot: Option<Snapshot>) -> Self {
+		self.fallback = snapshot;
+		self
+	}
+
+	/// Downloads the archives with the shared client (tls/ssl settings) instead of a default one
+	pub fn client(mut self, client: &Client) -> Self {
+		self.client = Some(client.clone());
+		self
+	}
+
+	/// Downloads the location into a temporary directory, or uses the embedded snapshot if it fails
+	pub fn fetch(&mut self) -> Result<TempDir, VerifierError> {
+		match (self.fetch_location(), self.fallback) {
+			(Err(e), Some(snapshot)) if snapshot.archive().is_some() => {
+				log::warn!(
+					"Unable to fetch {} ({}), using the embedded snapshot instead",
+					self.location,
+					e
+				);
+				self.location = snapshot.location();
+				self.fetch_location()
+			},
+			(result, _) => result,
+		}
+	}
+
+	/// Downloads the location into a temporary directory.
+	///
+	/// Locations are either `file://` directories, git repositories (https, ssh or `file://` bare repos), `.tar.gz` or
+	/// `.zip` archives (local or over http) or the embedded snapshots. The subdirectory inside a git repository or an
+	/// archive is given with `path`, except for GitHub urls where it can also be part of the url
+	/// (`https://github.com/<owner>/<repo>/<path>`).
+	fn fetch_location(&mut self) -> Result<TempDir, VerifierError> {
+		let dir = tempfile::tempdir().expect("Could not create temporary directory");
+
+		if let Some(snapshot) = Snapshot::from_location(&self.location) {
+			let archive = snapshot
+				.archive()
+				.ok_or_else(|| VerifierError::NoSnapshot(self.location.clone()))?;
+			log::debug!("Extracting embedded snapshot {} to {:?}", self.location, dir.path());
+			self.extract(ArchiveFormat::TarGz, archive, dir.path())?;
+			self.kind = Some(SourceKind::Embedded);
+			self.reference = None;
+			self.commit = snapshot.version().map(String::from);
+		}
+		else if let Some(format) = ArchiveFormat::from_location(&self.location) {
+			let archive = if self.location.scheme() == "file" {
+				std::fs::read(self.file_path()?)?
+			}
+			else if self.cache.as_ref().map_or(false, Cache::is_offline) {
+				return Err(VerifierError::Offline);
+			}
+			else {
+				log::debug!("Downloading archive {}", self.location);
+				self.client
+					.clone()
+					.unwrap_or_default()
+					.get(self.location.clone())
+					.send()?
+					.error_for_status()?
+					.bytes()?
+					.to_vec()
+			};
+			log::debug!("Extracting archive {} to {:?}", self.location, dir.path());
+			self.extract(format, &archive, dir.path())?;
+			self.kind = Some(SourceKind::Archive);
+		}
+		else if self.location.scheme() == "file" && !self.is_local_repository() {
+			if let Some(reference) = &self.reference {
+				log::warn!("Ignoring reference {} of local directory {}", reference, self.location);
+			}
+			let path = self.file_path()?;
+			log::debug!("COPYING {:?} to {:?}", path, dir.path());
+			utils::copy_dir_recursively(path, &dir).expect("Copy dir recursively failed");
+			self.kind = Some(SourceKind::Directory);
+		}
+		else {
+			let (repo_url, path) = self.repository();
+
+			log::debug!("Downloading repo {}", repo_url);
+			log::debug!("Path inside repo = {:?}", path);
+
+			// Clone repo to the cache or to a tempdir
+			let full_git_dir = tempfile::tempdir().expect("Could not create temporary directory");
+			let repository = match &self.cache {
+				Some(cache) => cache.repository(&repo_url, self.reference.as_deref())?,
+				None => clone_repository(&repo_url, full_git_dir.path())?,
+			};
+			self.checkout(&repository)?;
+
+			// Copy subfolder to the final tempdir
+			let workdir = repository.workdir().unwrap_or_else(|| full_git_dir.path());
+			log::debug!("Copying from {:?} to {:?}", workdir.join(&path), dir.path());
+			utils::copy_dir_recursively(workdir.join(path), dir.path())?;
+			self.kind = Some(SourceKind::Git);
+		}
+
+		Ok(dir)
+	}
+
+	/// Extracts the subdirectory `path` of an archive. Archives with a single top-level directory (like the ones
+	/// generated by GitHub) are extracted from inside that directory, and `path` is relative to it.
+	fn extract(&self, format: ArchiveFormat, archive: &[u8], into: &Path) -> Result<(), VerifierError> {
+		let extracted = tempfile::tempdir().expect("Could not create temporary directory");
+		match format {
+			ArchiveFormat::TarGz => {
+				tar::Archive::new(flate2::read::GzDecoder::new(archive)).unpack(extracted.path())?
+			},
+			ArchiveFormat::Zip => zip::ZipArchive::new(std::io::Cursor::new(archive))
+				.and_then(|mut zip| zip.extract(extracted.path()))
+				.map_err(|e| VerifierError::BadArchive(self.location.clone(), e.to_string()))?,
+		}
+
+		let entries = std::fs::read_dir(extracted.path())?.flatten().collect::<Vec<_>>();
+		let root = match entries.as_slice() {
+			[entry] if entry.path().is_dir() => entry.path(),
+			_ => extracted.path().to_path_buf(),
+		};
+		let root = match &self.path {
+			Some(path) => root.join(path),
+			None => root,
+		};
+		if !root.is_dir() {
+			return Err(VerifierError::BadArchive(
+				self.location.clone(),
+				format!(
+					"{:?} is not a directory of the archive",
+					self.path.clone().unwrap_or_default()
+				),
+			));
+		}
+		utils::copy_dir_recursively(root, into)
+	}
+
+	/// Path of a `file://` location, with its percent-encoded characters decoded
+	fn file_path(&self) -> Result<PathBuf, VerifierError> {
+		self.location
+			.to_file_path()
+			.map_err(|_| VerifierError::NotLocalPath(self.location.clone()))
+	}
+
+	/// Local directories are copied as they are, unless they are bare repositories or a reference is requested
+	fn is_local_repository(&self) -> bool {
+		self.file_path()
+			.ok()
+			.and_then(|path| Repository::open(path).ok())
+			.map_or(false, |repository| repository.is_bare() || self.reference.is_some())
+	}
+
+	/// Url of the git repository and subdirectory inside it
+	fn repository(&self) -> (String, PathBuf) {
+		if self.path.is_none() && self.location.domain() == Some("github.com") {
+			let mut url_iter = Path::new(self.location.path()).components().skip(1);
+			let owner = url_iter.next().unwrap().as_os_str().to_string_lossy().to_string();
+			let repo = url_iter.next().unwrap().as_os_str().to_string_lossy().to_string();
+			let path: PathBuf = url_iter.collect();
+			let repo_url = format!("https://github.com/{owner}/{repo}", owner = owner, repo = repo);
+			(repo_url, path)
+		}
+		else {
+			(self.location.to_string(), self.path.clone().unwrap_or_default())
+		}
+	}
+
+	/// Checks out the reference (or the default branch) and stores the resolved commit
+	fn checkout(&mut self, repository: &Repository) -> Result<(), VerifierError> {
+		let (object, moving) = resolve_reference(repository, self.reference.as_deref(), self.location.as_str())?;
+
+		repository.checkout_tree(&object, Some(CheckoutBuilder::new().force()))?;
+		repository.set_head_detached(object.id())?;
+
+		let commit = object.id().to_string();
+		if moving {
+			log::warn!(
+				"{} is not pinned to a tag or a commit, results may change when the branch moves (using commit {})",
+				self.location,
+				commit
+			);
+		}
+		log::debug!("Checked out commit {} of {}", commit, self.location);
+		self.commit = Some(commit);
+
+		Ok(())
+	}
+}
+
+/// Fetch options using the ssh agent or the git credential helpers when authentication is required
+pub fn fetch_options<'a>() -> Result<FetchOptions<'a>, VerifierError> {
+	let config = git2::Config::open_default()?;
+	let mut attempts = 0;
+	let mut callbacks = RemoteCallbacks::new();
+	callbacks.credentials(move |url, username, allowed| {
+		attempts += 1;
+		if attempts > 3 {
+			return Err(git2::Error::from_str("Authentication failed"));
+		}
+		if allowed.contains(CredentialType::SSH_KEY) {
+			Cred::ssh_key_from_agent(username.unwrap_or("git"))
+		}
+		else if allowed.contains(CredentialType::USER_PASS_PLAINTEXT) {
+			Cred::credential_helper(&config, url, username)
+		}
+		else {
+			Cred::default()
+		}
+	});
+
+	let mut fetch_options = FetchOptions::new();
+	fetch_options.remote_callbacks(callbacks);
+	Ok(fetch_options)
+}
+
+pub fn clone_repository(repo_url: &str, into: &Path) -> Result<Repository, VerifierError> {
+	Ok(RepoBuilder::new()
+		.fetch_options(fetch_options()?)
+		.clone(repo_url, into)?)
+}
+
+/// Commit a reference points to, and whether the reference can move (it is a branch)
+pub fn resolve_reference<'r>(
+	repository: &'r Repository,
+	reference: Option<&str>,
+	location: &str,
+) -> Result<(Object<'r>, bool), VerifierError> {
+	match reference {
+		Some(reference) => {
+			if let Ok(branch) = repository.find_reference(&format!("refs/remotes/origin/{}", reference)) {
+				Ok((branch.peel(ObjectType::Commit)?, true))
+			}
+			else if let Ok(tag) = repository.find_reference(&format!("refs/tags/{}", reference)) {
+				Ok((tag.peel(ObjectType::Commit)?, false))
+			}
+			else {
+				let object = repository
+					.revparse_single(reference)
+					.map_err(|_| VerifierError::UnknownReference(reference.to_string(), location.to_string()))?;
+				Ok((object.peel(ObjectType::Commit)?, false))
+			}
+		},
+		None => match repository.find_reference("refs/remotes/origin/HEAD") {
+			Ok(head) => Ok((head.peel(ObjectType::Commit)?, true)),
+			Err(_) => Ok((repository.head()?.peel(ObjectType::Commit)?, true)),
+		},
+	}
+}
+
+#[cfg(test)]
+mod tests {
+
+	use std::path::PathBuf;
+
+	use url::Url;
+
+	use crate::source::{ArchiveFormat, Source};
+
+	#[test]
+	fn test_source_reference() {
+		let location = Url::parse("https://github.com/org/repo@v2.0.0/path/to/model").unwrap();
+		let source = Source::new(&location, None, None);
+		assert_eq!(source.location.as_str(), "https://github.com/org/repo/path/to/model");
+		assert_eq!(source.reference.as_deref(), Some("v2.0.0"));
+
+		let source = Source::new(&location, Some(String::from("main")), None);
+		assert_eq!(source.reference.as_deref(), Some("main"));
+
+		let location = Url::parse("https://github.com/org/repo/path").unwrap();
+		let source = Source::new(&location, None, None);
+		assert_eq!(source.location, location);
+		assert_eq!(source.reference, None);
+	}
+
+	/// Archive with a single top-level directory, like the ones generated by GitHub
+	fn wrapped_archive() -> Vec<u8> {
+		let mut builder = tar::Builder::new(flate2::write::GzEncoder::new(
+			Vec::new(),
+			flate2::Compression::default(),
+		));
+		let schema = b"{}";
+		let mut header = tar::Header::new_gnu();
+		header.set_size(schema.len() as u64);
+		header.set_mode(0o644);
+		header.set_cksum();
+		builder
+			.append_data(
+				&mut header,
+				"beacon-v2-models-2.0.0/BEACON-V2-Model/beaconMap.json",
+				&schema[..],
+			)
+			.unwrap();
+		builder.into_inner().unwrap().finish().unwrap()
+	}
+
+	#[test]
+	fn test_extract_with_path() {
+		let location = Url::parse("https://example.org/beacon-v2-models-2.0.0.tar.gz").unwrap();
+		let into = tempfile::tempdir().unwrap();
+		let source = Source::new(&location, None, Some(PathBuf::from("BEACON-V2-Model")));
+		source
+			.extract(ArchiveFormat::TarGz, &wrapped_archive(), into.path())
+			.unwrap();
+		assert!(into.path().join("beaconMap.json").is_file());
+	}
+
+	#[test]
+	fn test_extract_without_path() {
+		let location = Url::parse("https://example.org/beacon-v2-models-2.0.0.tar.gz").unwrap();
+		let into = tempfile::tempdir().unwrap();
+		let source = Source::new(&location, None, None);
+		source
+			.extract(ArchiveFormat::TarGz, &wrapped_archive(), into.path())
+			.unwrap();
+		assert!(into.path().join("BEACON-V2-Model").join("beaconMap.json").is_file());
+	}
+
+	#[test]
+	fn test_extract_missing_path() {
+		let location = Url::parse("https://example.org/beacon-v2-models-2.0.0.tar.gz").unwrap();
+		let into = tempfile::tempdir().unwrap();
+		let source = Source::new(&location, None, Some(PathBuf::from("BEACON-V2-draft4-Model")));
+		let error = source
+			.extract(ArchiveFormat::TarGz, &wrapped_archive(), into.path())
+			.unwrap_err();
+		assert_eq!(
+			error.to_string(),
+			format!(
+				"Bad archive {}: \"BEACON-V2-draft4-Model\" is not a directory of the archive",
+				location
+			)
+		);
+	}
+
+	#[test]
+	fn test_fetch_encoded_paths() {
+		let dir = tempfile::tempdir().unwrap();
+		let archive = dir.path().join("beacon v2 models.tar.gz");
+		std::fs::write(&archive, wrapped_archive()).unwrap();
+		let models = dir.path().join("modèles");
+		std::fs::create_dir(&models).unwrap();
+		std::fs::write(models.join("beaconMap.json"), "{}").unwrap();
+
+		let location = Url::from_file_path(&archive).unwrap();
+		assert!(location.as_str().ends_with("beacon%20v2%20models.tar.gz"));
+		let fetched = Source::new(&location, None, Some(PathBuf::from("BEACON-V2-Model")))
+			.fetch()
+			.unwrap();
+		assert!(fetched.path().join("beaconMap.json").is_file());
+
+		let fetched = Source::new(&Url::from_directory_path(&models).unwrap(), None, None)
+			.fetch()
+			.unwrap();
+		assert!(fetched.path().join("beaconMap.json").is_file());
+	}
+}
@@ -0,0 +1,447 @@
+use std::collections::BTreeMap;
+use std::ffi::OsStr;
+use std::fmt;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+
+use reqwest::blocking::Client;
+use serde::Deserialize;
+use serde_json::json;
+use url::Url;
+
+use crate::error::VerifierError;
+use crate::interface::Granularity;
+use crate::model::Entity;
+use crate::output::EndpointReport;
+use crate::{utils, Json};
+
+/// Queries with known answers, for beacons that host a reference dataset
+#[derive(Debug, Clone, Deserialize)]
+pub struct Suite {
+	/// Name of the suite, by default the name of its file
+	#[serde(default)]
+	pub name: String,
+	pub queries: Vec<Query>,
+	#[serde(skip)]
+	path: PathBuf,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Query {
+	pub name: String,
+	/// Name or entry type of an entity of the model (e.g. `Individuals` or `individual`)
+	pub entity: String,
+	/// Request parameters (e.g. `referenceName`, `start`)
+	#[serde(default)]
+	pub parameters: BTreeMap<String, Json>,
+	/// Ids of filtering terms or filter objects (e.g. `{"id": "NCIT:C16576", "scope": "individual"}`)
+	#[serde(default)]
+	pub filters: Vec<Json>,
+	#[serde(default)]
+	pub granularity: Option<Granularity>,
+	/// Body of a POST request, the parameters, filters and granularity are added to its `query`
+	#[serde(default)]
+	pub body: Option<Json>,
+	#[serde(default)]
+	pub expect: Expectations,
+}
+
+/// Expected answer to a query
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Expectations {
+	#[serde(default)]
+	pub exists: Option<bool>,
+	/// Expected `numTotalResults`
+	#[serde(default)]
+	pub count: Option<u64>,
+	#[serde(default)]
+	pub count_range: Option<CountRange>,
+	/// Ids of records that must be in the results (only the ones of the page returned)
+	#[serde(default)]
+	pub ids_present: Vec<String>,
+	#[serde(default)]
+	pub ids_absent: Vec<String>,
+	#[serde(default)]
+	pub pointers: Vec<PointerAssertion>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CountRange {
+	#[serde(default)]
+	pub min: Option<u64>,
+	#[serde(default)]
+	pub max: Option<u64>,
+}
+
+/// Assertion on the value at a JSON pointer of the response
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PointerAssertion {
+	pub pointer: String,
+	#[serde(default)]
+	pub exists: Option<bool>,
+	#[serde(default)]
+	pub equals: Option<Json>,
+	/// Item of an array or substring of a string
+	#[serde(default)]
+	pub contains: Option<Json>,
+}
+
+impl Suite {
+	/// Loads a suite from a YAML (`.yaml` or `.yml`) or JSON file
+	pub fn load(path: &Path) -> Result<Self, VerifierError> {
+		let bad_suite = |e: String| VerifierError::BadSuite(path.to_path_buf(), e);
+		let file = File::open(path).map_err(|e| bad_suite(e.to_string()))?;
+		let mut suite: Self = match path.extension().and_then(OsStr::to_str) {
+			Some("yaml" | "yml") => serde_yaml::from_reader(file).map_err(|e| bad_suite(e.to_string()))?,
+			_ => serde_json::from_reader(file).map_err(|e| bad_suite(e.to_string()))?,
+		};
+		if suite.name.is_empty() {
+			suite.name = path
+				.file_stem()
+				.map_or_else(|| path.display().to_string(), |stem| stem.to_string_lossy().to_string());
+		}
+		suite.path = path.to_path_buf();
+		Ok(suite)
+	}
+
+	/// Runs the queries against the main endpoints of their entities, with a report for each one
+	pub fn run(&self, client: &Client, root_url: &Url, entities: &[Entity]) -> Vec<EndpointReport> {
+		self.queries
+			.iter()
+			.map(|query| self.run_query(client, query, root_url, entities))
+			.collect()
+	}
+
+	fn run_query(&self, client: &Client, query: &Query, root_url: &Url, entities: &[Entity]) -> EndpointReport {
+		let entity = entities.iter().find(|entity| {
+			entity.name.eq_ignore_ascii_case(&query.entity) || entity.entry_type.eq_ignore_ascii_case(&query.entity)
+		});
+		let main_url = entity
+			.and_then(|entity| utils::expand_template(&entity.url, &[]))
+			.and_then(|url| Url::parse(&url).ok())
+			.map(|url| utils::url_join(root_url, &url));
+		let (entity, main_url) = match (entity, main_url) {
+			(Some(entity), Some(main_url)) => (entity, main_url),
+			_ => {
+				return EndpointReport::new(&query.entity, &query.name, root_url.clone())
+					.suite(&self.name)
+					.null(VerifierError::BadSuite(
+						self.path.clone(),
+						format!("{} is not an entity of the model", query.entity),
+					))
+			},
+		};
+
+		let url = query.url(&main_url);
+		log::info!("Running {:?} of test suite {:?} ({})", query.name, self.name, url);
+		let report = EndpointReport::new(&entity.name, &query.name, url.clone()).suite(&self.name);
+		let response = match query.post_body() {
+			Some(body) => utils::post_url(client, &url, &body),
+			None => utils::ping_url(client, &url),
+		};
+		match response.and_then(|response| query.expect.check(&response, &entity.id_pointer)) {
+			Ok(()) => report.ok(None),
+			Err(e @ VerifierError::UnexpectedAnswer(_)) => report.error(e),
+			Err(e) => report.null(e),
+		}
+	}
+}
+
+impl Query {
+	/// Whether the query is sent as a POST request: it has a body or a filter that is not just an id
+	fn is_post(&self) -> bool {
+		self.body.is_some() || !self.filters.iter().all(Json::is_string)
+	}
+
+	/// Url of the request, with the parameters, filters and granularity in the query string of GET requests
+	fn url(&self, main_url: &Url) -> Url {
+		let mut url = main_url.clone();
+		if self.is_post() {
+			return url;
+		}
+		for (key, value) in &self.parameters {
+			let value = match value {
+				Json::String(value) => value.clone(),
+				Json::Array(values) => values
+					.iter()
+					.map(|value| value.as_str().map_or_else(|| value.to_string(), String::from))
+					.collect::<Vec<_>>()
+					.join(","),
+				value => value.to_string(),
+			};
+			url.query_pairs_mut().append_pair(key, &value);
+		}
+		if !self.filters.is_empty() {
+			let filters = self.filters.iter().filter_map(Json::as_str).collect::<Vec<_>>();
+			url.query_pairs_mut().append_pair("filters", &filters.join(","));
+		}
+		if let Some(granularity) = self
+			.granularity
+			.and_then(|granularity| serde_json::to_value(granularity).ok())
+		{
+			url.query_pairs_mut()
+				.append_pair("requestedGranularity", granularity.as_str().unwrap_or_default());
+		}
+		url
+	}
+
+	/// Body of a POST request
+	fn post_body(&self) -> Option<Json> {
+		if !self.is_post() {
+			return None;
+		}
+		let mut body = self
+			.body
+			.clone()
+			.unwrap_or_else(|| json!({"meta": {"apiVersion": "2.0"}}));
+		let mut query = body["query"].as_object().cloned().unwrap_or_default();
+		if !self.parameters.is_empty() {
+			query.insert(String::from("requestParameters"), json!(self.parameters));
+		}
+		if !self.filters.is_empty() {
+			let filters = self
+				.filters
+				.iter()
+				.map(|filter| match filter {
+					Json::String(id) => json!({ "id": id }),
+					filter => filter.clone(),
+				})
+				.collect();
+			query.insert(String::from("filters"), Json::Array(filters));
+		}
+		if let Some(granularity) = self.granularity {
+			query.insert(String::from("requestedGranularity"), json!(granularity));
+		}
+		if let Some(body) = body.as_object_mut() {
+			body.insert(String::from("query"), Json::Object(query));
+		}
+		Some(body)
+	}
+}
+
+impl Expectations {
+	/// Checks the answer of a response, `id_pointer` is the JSON pointer to the ids of the records
+	fn check(&self, response: &Json, id_pointer: &str) -> Result<(), VerifierError> {
+		let mut errors = Vec::new();
+		let summary = &response["responseSummary"];
+		if let Some(exists) = self.exists {
+			if summary["exists"].as_bool() != Some(exists) {
+				errors.push(format!("exists is {} instead of {}", summary["exists"], exists));
+			}
+		}
+		let count = summary["numTotalResults"].as_u64();
+		if let Some(expected) = self.count {
+			if count != Some(expected) {
+				errors.push(format!(
+					"numTotalResults is {} instead of {}",
+					summary["numTotalResults"], expected
+				));
+			}
+		}
+		if let Some(range) = &self.count_range {
+			if !count.map_or(false, |count| range.contains(count)) {
+				errors.push(format!(
+					"numTotalResults is {} instead of {}",
+					summary["numTotalResults"], range
+				));
+			}
+		}
+
+		let ids = utils::records(response)
+			.into_iter()
+			.filter_map(|record| utils::record_id(record, id_pointer))
+			.collect::<Vec<_>>();
+		for id in self.ids_present.iter().filter(|id| !ids.contains(id)) {
+			errors.push(format!("{} is not in the results", id));
+		}
+		for id in self.ids_absent.iter().filter(|id| ids.contains(id)) {
+			errors.push(format!("{} is in the results", id));
+		}
+
+		errors.extend(
+			self.pointers
+				.iter()
+				.filter_map(|assertion| assertion.check(response).err()),
+		);
+
+		if errors.is_empty() {
+			Ok(())
+		}
+		else {
+			Err(VerifierError::UnexpectedAnswer(errors.join("\n")))
+		}
+	}
+}
+
+impl CountRange {
+	fn contains(&self, count: u64) -> bool {
+		self.min.map_or(true, |min| count >= min) && self.max.map_or(true, |max| count <= max)
+	}
+}
+
+impl fmt::Display for CountRange {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match (self.min, self.max) {
+			(Some(min), Some(max)) => write!(f, "between {} and {}", min, max),
+			(Some(min), None) => write!(f, "at least {}", min),
+			(None, Some(max)) => write!(f, "at most {}", max),
+			(None, None) => write!(f, "any count"),
+		}
+	}
+}
+
+impl PointerAssertion {
+	fn check(&self, response: &Json) -> Result<(), String> {
+		let value = response.pointer(&self.pointer);
+		let found = || value.map_or_else(|| String::from("missing"), Json::to_string);
+		if let Some(exists) = self.exists {
+			if value.is_some() != exists {
+				return Err(format!(
+					"{} is {} instead of {}",
+					self.pointer,
+					found(),
+					if exists { "present" } else { "missing" }
+				));
+			}
+		}
+		if let Some(expected) = &self.equals {
+			if value != Some(expected) {
+				return Err(format!("{} is {} instead of {}", self.pointer, found(), expected));
+			}
+		}
+		if let Some(expected) = &self.contains {
+			let contains = match value {
+				Some(Json::Array(values)) => values.contains(expected),
+				Some(Json::String(value)) => expected.as_str().map_or(false, |expected| value.contains(expected)),
+				_ => false,
+			};
+			if !contains {
+				return Err(format!(
+					"{} is {} and does not contain {}",
+					self.pointer,
+					found(),
+					expected
+				));
+			}
+		}
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+
+	use serde_json::json;
+	use url::Url;
+
+	use crate::suites::{Expectations, Query};
+
+	#[test]
+	fn test_query() {
+		let query: Query = serde_yaml::from_str(
+			"
+name: Females
+entity: individuals
+parameters: {sex: 'NCIT:C16576'}
+filters: ['NCIT:C20197']
+granularity: count
+expect:
+  exists: true
+  count_range: {min: 10}
+",
+		)
+		.unwrap();
+		let url = query.url(&Url::parse("https://example.org/api/individuals").unwrap());
+		assert_eq!(
+			url.as_str(),
+			"https://example.org/api/individuals?sex=NCIT%3AC16576&filters=NCIT%3AC20197&requestedGranularity=count"
+		);
+		assert!(query.post_body().is_none());
+	}
+
+	#[test]
+	fn test_query_post_body() {
+		let query: Query = serde_json::from_value(json!({
+			"name": "Females",
+			"entity": "individuals",
+			"filters": [{"id": "NCIT:C20197", "scope": "individual"}, "NCIT:C16576"],
+			"granularity": "record"
+		}))
+		.unwrap();
+		assert_eq!(
+			query.post_body().unwrap()["query"],
+			json!({
+				"filters": [{"id": "NCIT:C20197", "scope": "individual"}, {"id": "NCIT:C16576"}],
+				"requestedGranularity": "record"
+			})
+		);
+	}
+
+	#[test]
+	fn test_query_unknown_field() {
+		let error = serde_json::from_value::<Query>(json!({
+			"name": "Females",
+			"entity": "individuals",
+			"expect": {"exist": true}
+		}))
+		.unwrap_err();
+		assert!(error.to_string().starts_with("unknown field `exist`"), "{}", error);
+	}
+
+	#[test]
+	fn test_expectations() {
+		let response = json!({
+			"meta": {"returnedGranularity": "record"},
+			"responseSummary": {"exists": true, "numTotalResults": 2},
+			"response": {"resultSets": [{"results": [{"id": "ind1"}, {"id": "ind2"}]}]}
+		});
+		let expect = |expectations| serde_json::from_value::<Expectations>(expectations).unwrap();
+
+		assert!(expect(json!({
+			"exists": true,
+			"count": 2,
+			"count_range": {"min": 1, "max": 5},
+			"ids_present": ["ind1"],
+			"ids_absent": ["ind3"],
+			"pointers": [
+				{"pointer": "/meta/returnedGranularity", "equals": "record"},
+				{"pointer": "/meta/beaconId", "exists": false},
+				{"pointer": "/response/resultSets/0/results", "contains": {"id": "ind2"}}
+			]
+		}))
+		.check(&response, "/id")
+		.is_ok());
+		assert_eq!(
+			expect(json!({"exists": false, "count_range": {"min": 3}}))
+				.check(&response, "/id")
+				.unwrap_err()
+				.to_string(),
+			"The answer is not the expected one: exists is true instead of false\nnumTotalResults is 2 instead of at \
+			 least 3"
+		);
+		assert_eq!(
+			expect(json!({"ids_present": ["ind3"], "ids_absent": ["ind2"]}))
+				.check(&response, "/id")
+				.unwrap_err()
+				.to_string(),
+			"The answer is not the expected one: ind3 is not in the results\nind2 is in the results"
+		);
+		assert_eq!(
+			expect(json!({"pointers": [
+				{"pointer": "/meta/returnedGranularity", "equals": "count"},
+				{"pointer": "/meta/beaconId", "exists": true},
+				{"pointer": "/response/resultSets/0/results/0/id", "contains": "ind2"}
+			]}))
+			.check(&response, "/id")
+			.unwrap_err()
+			.to_string(),
+			"The answer is not the expected one: /meta/returnedGranularity is \"record\" instead of \
+			 \"count\"\n/meta/beaconId is missing instead of present\n/response/resultSets/0/results/0/id is \"ind1\" \
+			 and does not contain \"ind2\""
+		);
+	}
+}
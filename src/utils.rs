@@ -1,17 +1,16 @@
-use std::path::{Path, PathBuf};
+use std::path::{Component, Path, PathBuf};
 use std::rc::Rc;
 
 use jsonschema::JSONSchema;
+use reqwest::blocking::Client;
 use url::Url;
 
 use crate::error::VerifierError;
-use crate::interface::{BeaconResultSetResponse, EntityResult};
+use crate::resolver::LocalResolver;
 // TODO: Use filtering terms
 // use crate::interface::FilteringTerm;
 use crate::{error, Json};
 
-use clap::Parser;
-
 pub fn copy_dir_recursively<U: AsRef<Path>, V: AsRef<Path>>(from: U, to: V) -> Result<(), VerifierError> {
 	let mut stack = vec![PathBuf::from(from.as_ref())];
 
@@ -60,14 +59,27 @@ pub fn copy_dir_recursively<U: AsRef<Path>, V: AsRef<Path>>(from: U, to: V) -> R
 	Ok(())
 }
 
-pub fn ping_url(endpoint_url: &Url) -> Result<Json, VerifierError> {
+/// Removes `.` and `..` from a relative path without touching the filesystem
+pub fn normalize_path(path: &Path) -> PathBuf {
+	let mut normalized = PathBuf::new();
+	for component in path.components() {
+		match component {
+			Component::ParentDir => {
+				normalized.pop();
+			},
+			Component::CurDir => (),
+			component => normalized.push(component),
+		}
+	}
+	normalized
+}
 
-        let matches = crate::Args::parse();
-        // Build client
-        let client = reqwest::blocking::Client::builder()
-            .danger_accept_invalid_certs(matches.ssl_no_verify)
-            .build()?;
+/// Client used for all the requests, which does not validate tls/ssl certificates with `ssl_no_verify`
+pub fn client(ssl_no_verify: bool) -> Result<Client, VerifierError> {
+	Ok(Client::builder().danger_accept_invalid_certs(ssl_no_verify).build()?)
+}
 
+pub fn ping_url(client: &Client, endpoint_url: &Url) -> Result<Json, VerifierError> {
 	// Query endpoint
 	let response = match client.get(endpoint_url.clone()).send() {
 		Ok(response) if response.status().is_success() => response,
@@ -106,6 +118,31 @@ pub fn ping_url(endpoint_url: &Url) -> Result<Json, VerifierError> {
 	Ok(response_json)
 }
 
+/// Status and body (`None` if it is not JSON) of the response to a GET request, whatever the status is
+pub fn request_url(client: &Client, endpoint_url: &Url) -> Result<(u16, Option<Json>), VerifierError> {
+	let response = client.get(endpoint_url.clone()).send()?;
+	let status = response.status().as_u16();
+	Ok((status, response.json().ok()))
+}
+
+/// Response to a POST request with a JSON body
+pub fn post_url(client: &Client, endpoint_url: &Url, body: &Json) -> Result<Json, VerifierError> {
+	let response = client.post(endpoint_url.clone()).json(body).send()?;
+	if !response.status().is_success() {
+		return Err(VerifierError::UnresponsiveEndpoint(endpoint_url.clone()));
+	}
+	response.json().map_err(|_| VerifierError::ResponseIsNotJson)
+}
+
+/// Status of a HEAD request (or of a GET request if HEAD fails, since some servers do not support it), without
+/// reading the body
+pub fn url_status(client: &Client, url: &Url) -> Result<u16, VerifierError> {
+	match client.head(url.clone()).send() {
+		Ok(response) if response.status().as_u16() < 400 => Ok(response.status().as_u16()),
+		_ => Ok(client.get(url.clone()).send()?.status().as_u16()),
+	}
+}
+
 pub fn url_join(url1: &Url, url2: &Url) -> Url {
 	let mut replaced_url = url1.clone();
 	let new_path: PathBuf = PathBuf::from(replaced_url.path())
@@ -113,15 +150,141 @@ pub fn url_join(url1: &Url, url2: &Url) -> Url {
 		.chain(Path::new(url2.path()).components().skip(1))
 		.collect();
 	replaced_url.set_path(new_path.to_str().unwrap_or(""));
+	replaced_url.set_query(url2.query());
 	replaced_url
 }
 
-pub fn replace_vars(url: &Url, vars: Vec<(&str, &str)>) -> Url {
-	let mut url_string = url.to_string();
-	for (var_key, var_val) in vars {
-		url_string = url.to_string().replace(&format!("%7B{}%7D", var_key), var_val);
+/// Variables that can be used inside the url templates of the beacon map, the ones without a value are removed
+pub const URL_VARIABLES: &[&str] = &[
+	"id",
+	"skip",
+	"limit",
+	"requestedSchema",
+	"requestedGranularity",
+	"includeResultsetResponses",
+	"filters",
+];
+
+/// Part of an RFC 6570 url template
+enum TemplatePart<'a> {
+	Literal(&'a str),
+	/// Operator and variables (with their prefix modifier) of an expression
+	Expression(Option<char>, Vec<(&'a str, Option<usize>)>),
+}
+
+/// `None` if the template is malformed
+fn parse_template(template: &str) -> Option<Vec<TemplatePart<'_>>> {
+	let mut parts = Vec::new();
+	let mut rest = template;
+	while let Some(start) = rest.find(|c| c == '{' || c == '}') {
+		if rest[start..].starts_with('}') {
+			return None;
+		}
+		let end = rest[start..].find('}')? + start;
+		let expression = &rest[start + 1..end];
+		let (operator, list) = match expression.chars().next()? {
+			c @ ('+' | '#' | '.' | '/' | ';' | '?' | '&') => (Some(c), &expression[1..]),
+			_ => (None, expression),
+		};
+		let mut variables = Vec::new();
+		for varspec in list.split(',') {
+			let (name, prefix) = match varspec.split_once(':') {
+				Some((name, length)) => (name, Some(length.parse::<usize>().ok()?)),
+				None => (varspec.strip_suffix('*').unwrap_or(varspec), None),
+			};
+			if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.') {
+				return None;
+			}
+			variables.push((name, prefix));
+		}
+		parts.push(TemplatePart::Literal(&rest[..start]));
+		parts.push(TemplatePart::Expression(operator, variables));
+		rest = &rest[end + 1..];
+	}
+	parts.push(TemplatePart::Literal(rest));
+	Some(parts)
+}
+
+/// Variables used in a url template, `None` if the template is malformed
+pub fn template_variables(template: &str) -> Option<Vec<String>> {
+	Some(
+		parse_template(template)?
+			.into_iter()
+			.flat_map(|part| match part {
+				TemplatePart::Literal(_) => Vec::new(),
+				TemplatePart::Expression(_, variables) => {
+					variables.into_iter().map(|(name, _)| name.to_string()).collect()
+				},
+			})
+			.collect(),
+	)
+}
+
+/// Percent-encodes everything but the unreserved characters (and the reserved ones if `allow_reserved`)
+fn percent_encode(value: &str, allow_reserved: bool) -> String {
+	value
+		.bytes()
+		.map(|b| {
+			let c = b as char;
+			if c.is_ascii_alphanumeric() || "-._~".contains(c) || (allow_reserved && ":/?#[]@!$&'()*+,;=".contains(c)) {
+				c.to_string()
+			}
+			else {
+				format!("%{:02X}", b)
+			}
+		})
+		.collect()
+}
+
+/// Expands an RFC 6570 url template with string values, `None` if the template is malformed
+pub fn expand_template(template: &str, vars: &[(&str, &str)]) -> Option<String> {
+	let mut expanded = String::new();
+	for part in parse_template(template)? {
+		let (operator, variables) = match part {
+			TemplatePart::Literal(literal) => {
+				expanded.push_str(literal);
+				continue;
+			},
+			TemplatePart::Expression(operator, variables) => (operator, variables),
+		};
+
+		// First character, separator, named and reserved characters allowed
+		let (first, separator, named, allow_reserved) = match operator {
+			Some('+') => ("", ",", false, true),
+			Some('#') => ("#", ",", false, true),
+			Some('.') => (".", ".", false, false),
+			Some('/') => ("/", "/", false, false),
+			Some(';') => (";", ";", true, false),
+			Some('?') => ("?", "&", true, false),
+			Some('&') => ("&", "&", true, false),
+			_ => ("", ",", false, false),
+		};
+
+		let values = variables
+			.into_iter()
+			.filter_map(|(name, prefix)| {
+				let (_, value) = vars.iter().find(|(var_name, _)| *var_name == name)?;
+				let value = prefix.map_or_else(|| value.to_string(), |length| value.chars().take(length).collect());
+				let value = percent_encode(&value, allow_reserved);
+				Some(match (named, value.is_empty()) {
+					(false, _) => value,
+					(true, true) if operator == Some(';') => name.to_string(),
+					(true, _) => format!("{}={}", name, value),
+				})
+			})
+			.collect::<Vec<_>>();
+
+		if !values.is_empty() {
+			expanded.push_str(first);
+			expanded.push_str(&values.join(separator));
+		}
 	}
-	Url::parse(&url_string).unwrap()
+	Some(expanded)
+}
+
+/// Escapes a key to be used inside a JSON pointer (RFC 6901)
+pub fn pointer_token(key: &str) -> String {
+	key.replace('~', "~0").replace('/', "~1")
 }
 
 // TODO: Filtering terms
@@ -136,27 +299,86 @@ pub fn replace_vars(url: &Url, vars: Vec<(&str, &str)>) -> Url {
 // 	}
 // }
 
-pub fn get_ids(root_url: &Url, entity_url: &Url) -> Result<Vec<String>, VerifierError> {
+/// JSON pointer of a field name, JSON pointers are kept as they are
+pub fn id_pointer(field: &str) -> String {
+	if field.starts_with('/') {
+		field.to_string()
+	}
+	else {
+		format!("/{}", pointer_token(field))
+	}
+}
+
+/// Location of the id of the records of a schema: the first usual id property it has or, otherwise, its first
+/// required `...Id` property
+pub fn default_id_pointer(schema: &Json) -> String {
+	let properties = schema["properties"].as_object();
+	let has_property = |name: &str| properties.map_or(false, |properties| properties.contains_key(name));
+	let field = ["id", "variantInternalId", "cohortId"]
+		.into_iter()
+		.find(|name| has_property(name))
+		.map(String::from)
+		.or_else(|| {
+			schema["required"]
+				.as_array()
+				.into_iter()
+				.flatten()
+				.filter_map(Json::as_str)
+				.find(|name| name.ends_with("Id"))
+				.map(String::from)
+		})
+		.unwrap_or_else(|| String::from("id"));
+	id_pointer(&field)
+}
+
+/// Id of a record, numeric ids are converted to strings
+pub fn record_id(record: &Json, id_pointer: &str) -> Option<String> {
+	match record.pointer(id_pointer)? {
+		Json::String(id) => Some(id.clone()),
+		Json::Number(id) => Some(id.to_string()),
+		_ => None,
+	}
+}
+
+/// Records of a response, inside its result sets or collections
+pub fn records(response: &Json) -> Vec<&Json> {
+	response["response"]["resultSets"]
+		.as_array()
+		.into_iter()
+		.flatten()
+		.flat_map(|result_set| result_set["results"].as_array().into_iter().flatten())
+		.chain(response["response"]["collections"].as_array().into_iter().flatten())
+		.collect()
+}
+
+/// Ids of the results of an endpoint, `NoIds` if there are none
+pub fn get_ids(
+	client: &Client,
+	root_url: &Url,
+	entity_url: &Url,
+	id_pointer: &str,
+) -> Result<Vec<String>, VerifierError> {
 	let endpoint_url = url_join(root_url, entity_url);
-	match ping_url(&endpoint_url) {
-		Ok(response) => {
-			let resultset_response: BeaconResultSetResponse =
-				serde_json::from_value(response).map_err(|e| -> VerifierError { e.into() })?;
-			Ok(resultset_response
-				.response
-				.result_sets
-				.iter()
-				.flat_map(|rs| rs.results.iter().map(EntityResult::id))
-				.collect())
-		},
-		Err(e) => {
-			log::error!("Error endpoint with the ids: {:?}", e);
-			Ok(Vec::new())
-		},
+	let response = ping_url(client, &endpoint_url).map_err(|e| {
+		log::error!("Error endpoint with the ids: {:?}", e);
+		e
+	})?;
+	let ids = records(&response)
+		.into_iter()
+		.filter_map(|result| record_id(result, id_pointer))
+		.collect::<Vec<_>>();
+	if ids.is_empty() {
+		return Err(VerifierError::NoIds(id_pointer.to_string()));
 	}
+	Ok(ids)
 }
 
 pub fn valid_schema(json_schema: &JSONSchema, instance: &Json) -> Result<Json, VerifierError> {
+	valid_schema_at(json_schema, instance, "")
+}
+
+/// Validates an instance found at `pointer` of a response, which prefixes the paths of the errors
+pub fn valid_schema_at(json_schema: &JSONSchema, instance: &Json, pointer: &str) -> Result<Json, VerifierError> {
 	match json_schema.validate(instance) {
 		Ok(_) => {
 			log::info!("VALID");
@@ -165,23 +387,35 @@ pub fn valid_schema(json_schema: &JSONSchema, instance: &Json) -> Result<Json, V
 		Err(errors) => {
 			log::error!("NOT VALID:");
 			let mut er = String::new();
+			let mut only_additional_properties = true;
 			errors.into_iter().for_each(|e| {
+				only_additional_properties &=
+					matches!(e.kind, jsonschema::error::ValidationErrorKind::AdditionalProperties { .. });
 				log::error!(
 					"   ERROR: {:?} - {} ({})",
 					e.kind,
 					e.to_string(),
 					e.instance_path.to_string(),
 				);
-				er.push_str(&format!("{} ({})", e, e.instance_path));
+				er.push_str(&format!("{} ({}{})", e, pointer, e.instance_path));
 				er.push('\n');
 			});
-			Err(VerifierError::BadResponse(er))
+			if only_additional_properties {
+				Err(VerifierError::UnexpectedProperties(er))
+			}
+			else {
+				Err(VerifierError::BadResponse(er))
+			}
 		},
 	}
 }
 
-pub fn compile_schema(schema: &Json) -> Rc<JSONSchema> {
-	let result_sets_schema = match jsonschema::JSONSchema::options().with_meta_schemas().compile(schema) {
+pub fn compile_schema(schema: &Json, resolver: &LocalResolver) -> Rc<JSONSchema> {
+	let result_sets_schema = match jsonschema::JSONSchema::options()
+		.with_meta_schemas()
+		.with_resolver(resolver.clone())
+		.compile(schema)
+	{
 		Ok(schema) => schema,
 		Err(e) => {
 			log::error!("{:?}", e);
@@ -196,16 +430,143 @@ pub fn compile_schema(schema: &Json) -> Rc<JSONSchema> {
 #[cfg(test)]
 mod tests {
 
+	use std::io::{BufRead, BufReader, Write};
+	use std::net::TcpListener;
+
+	use serde_json::json;
 	use url::Url;
 
-	use crate::utils::replace_vars;
+	use crate::checks::Severity;
+	use crate::error::VerifierError;
+	use crate::utils::{
+		client, default_id_pointer, expand_template, get_ids, request_url, template_variables, url_status,
+	};
+
+	/// Serves one response for each request method, in the order they are expected
+	fn serve(responses: &[(&'static str, &'static str)]) -> Url {
+		let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+		let url = Url::parse(&format!("http://{}/api", listener.local_addr().unwrap())).unwrap();
+		let responses = responses.to_vec();
+		std::thread::spawn(move || {
+			for (method, response) in responses {
+				let (mut stream, _) = listener.accept().unwrap();
+				let mut request_line = String::new();
+				let mut reader = BufReader::new(stream.try_clone().unwrap());
+				reader.read_line(&mut request_line).unwrap();
+				assert!(request_line.starts_with(method), "{}", request_line);
+				let mut line = String::new();
+				while reader.read_line(&mut line).unwrap() > 2 {
+					line.clear();
+				}
+				stream.write_all(response.as_bytes()).unwrap();
+			}
+		});
+		url
+	}
 
 	#[test]
-	fn test_replace_vars() {
-		let replaced = replace_vars(
-			&Url::parse("https://google.com/biosamples/{id}").unwrap(),
-			vec![("id", "my_id")],
+	fn test_request_url() {
+		let url = serve(&[(
+			"GET",
+			"HTTP/1.1 404 Not Found\r\nContent-Type: application/json\r\nContent-Length: 24\r\nConnection: \
+			 close\r\n\r\n{\"error\": {\"code\": 404}}",
+		)]);
+		let (status, body) = request_url(&client(false).unwrap(), &url).unwrap();
+		assert_eq!(status, 404);
+		assert_eq!(body, Some(json!({"error": {"code": 404}})));
+	}
+
+	#[test]
+	fn test_get_ids() {
+		let response = "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: 82\r\nConnection: \
+		                close\r\n\r\n{\"response\": {\"resultSets\": [{\"results\": [{\"id\": \"ind1\"}, \
+		                {\"individualId\": 2}]}]}}";
+		let url = serve(&[("GET", response), ("GET", response)]);
+		let root_url = url.join("/").unwrap();
+		let client = client(false).unwrap();
+		assert_eq!(
+			get_ids(&client, &root_url, &url, "/id").unwrap(),
+			vec![String::from("ind1")]
+		);
+
+		let error = get_ids(&client, &root_url, &url, "/sampleId").unwrap_err();
+		assert!(matches!(&error, VerifierError::NoIds(pointer) if pointer == "/sampleId"));
+		assert_eq!(error.severity(), Some(Severity::Warning));
+	}
+
+	#[test]
+	fn test_url_status_without_head() {
+		let url = serve(&[
+			(
+				"HEAD",
+				"HTTP/1.1 501 Not Implemented\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+			),
+			(
+				"GET",
+				"HTTP/1.1 200 OK\r\nContent-Length: 2\r\nConnection: close\r\n\r\nok",
+			),
+		]);
+		assert_eq!(url_status(&client(false).unwrap(), &url).unwrap(), 200);
+	}
+
+	#[test]
+	fn test_expand_template() {
+		let expand = |template| expand_template(template, &[("id", "my_id"), ("skip", "0"), ("limit", "10")]);
+		assert_eq!(
+			expand("https://google.com/biosamples/{id}"),
+			Some(String::from("https://google.com/biosamples/my_id"))
+		);
+		assert_eq!(
+			expand("https://google.com/individuals/{id}/biosamples{?skip,limit}"),
+			Some(String::from(
+				"https://google.com/individuals/my_id/biosamples?skip=0&limit=10"
+			))
+		);
+		assert_eq!(
+			expand("https://google.com/individuals{/id}{?unknown}{&limit}"),
+			Some(String::from("https://google.com/individuals/my_id&limit=10"))
+		);
+		assert_eq!(
+			expand("https://google.com/{id:2}{;skip}"),
+			Some(String::from("https://google.com/my;skip=0"))
+		);
+		assert_eq!(expand("https://google.com/{id"), None);
+		assert_eq!(
+			expand_template("https://google.com/biosamples/{id}", &[("id", "NCIT:C20197/a b")]),
+			Some(String::from("https://google.com/biosamples/NCIT%3AC20197%2Fa%20b"))
+		);
+		assert_eq!(
+			expand_template("https://google.com/{+id}", &[("id", "a/b c")]),
+			Some(String::from("https://google.com/a/b%20c"))
+		);
+	}
+
+	#[test]
+	fn test_default_id_pointer() {
+		assert_eq!(default_id_pointer(&json!({"properties": {"id": {}, "name": {}}})), "/id");
+		assert_eq!(
+			default_id_pointer(&json!({"properties": {"variantInternalId": {}}})),
+			"/variantInternalId"
+		);
+		assert_eq!(
+			default_id_pointer(&json!({"properties": {"runId": {}}, "required": ["name", "runId"]})),
+			"/runId"
+		);
+		assert_eq!(default_id_pointer(&json!({})), "/id");
+	}
+
+	#[test]
+	fn test_template_variables() {
+		assert_eq!(
+			template_variables("https://google.com/individuals/{id}/biosamples"),
+			Some(vec![String::from("id")])
+		);
+		assert_eq!(template_variables("https://google.com/individuals"), Some(vec![]));
+		assert_eq!(template_variables("https://google.com/individuals/{id"), None);
+		assert_eq!(template_variables("https://google.com/individuals/id}"), None);
+		assert_eq!(
+			template_variables("https://google.com/individuals/{id}{?skip,limit:3}"),
+			Some(vec![String::from("id"), String::from("skip"), String::from("limit")])
 		);
-		assert_eq!(replaced.to_string(), "https://google.com/biosamples/my_id");
 	}
 }
@@ -0,0 +1,279 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use chrono::SubsecRound;
+use reqwest::blocking::Client;
+use url::Url;
+
+use crate::error::VerifierError;
+use crate::output::{BeaconOutput, EndpointReport, Output};
+use crate::resolver::LocalResolver;
+use crate::{utils, Json};
+
+/// Schema of the v1 info (`GET /`)
+const INFO_SCHEMA: &str = include_str!("../assets/v1/beaconInfo.json");
+/// Schema of the v1 allele response (`GET /query`)
+const ALLELE_RESPONSE_SCHEMA: &str = include_str!("../assets/v1/alleleResponse.json");
+
+/// Beacon implementing the v1 API, with its info in `/` and allele requests in `/query`
+pub struct BeaconV1 {
+	client: Client,
+	name: String,
+	url: Url,
+	info: Json,
+}
+
+impl BeaconV1 {
+	/// The beacon, if its root answers with a v1 info (an `apiVersion` 1.x and no `meta`)
+	pub fn detect(client: &Client, url: &Url) -> Option<Self> {
+		let info = utils::ping_url(client, url).ok()?;
+		Self::from_info(client, url, info)
+	}
+
+	fn from_info(client: &Client, url: &Url, info: Json) -> Option<Self> {
+		let api_version = info["apiVersion"].as_str()?;
+		if info.get("meta").is_some() || api_version.trim_start_matches('v').split('.').next() != Some("1") {
+			return None;
+		}
+		Some(Self {
+			client: client.clone(),
+			name: info["name"]
+				.as_str()
+				.map_or_else(|| String::from("Unknown name (bad /)"), String::from),
+			url: url.clone(),
+			info,
+		})
+	}
+
+	fn query_url(&self, request: &BTreeMap<String, Json>) -> Url {
+		let mut url = self.url.clone();
+		url.set_path(Path::new(self.url.path()).join("query").to_str().unwrap_or(""));
+		for (key, value) in request {
+			let values = value.as_array().cloned().unwrap_or_else(|| vec![value.clone()]);
+			for value in values {
+				let value = value.as_str().map_or_else(|| value.to_string(), String::from);
+				url.query_pairs_mut().append_pair(key, &value);
+			}
+		}
+		url
+	}
+
+	/// First sample allele request of the info, the queries cannot be tested without one
+	fn allele_request(&self) -> Option<BTreeMap<String, Json>> {
+		self.info["sampleAlleleRequests"][0]
+			.as_object()
+			.map(|request| request.clone().into_iter().collect())
+	}
+
+	/// Checks that the allele response identifies the beacon like the info
+	fn check_identity(&self, response: &Json) -> Result<(), VerifierError> {
+		let mut errors = Vec::new();
+		for (field, info_field) in [("beaconId", "id"), ("apiVersion", "apiVersion")] {
+			if let (Some(returned), Some(expected)) = (response[field].as_str(), self.info[info_field].as_str()) {
+				if returned != expected {
+					errors.push(format!("{} is {} but it is {} in /", field, returned, expected));
+				}
+			}
+		}
+		if errors.is_empty() {
+			Ok(())
+		}
+		else {
+			Err(VerifierError::IdentityMismatch(errors.join("\n")))
+		}
+	}
+
+	fn validate_query(&self, url: Url, schema: &jsonschema::JSONSchema) -> EndpointReport {
+		log::info!("Validating \"Query\" ({})", url);
+		let report = EndpointReport::new("Query", "Allele request", url.clone());
+		let response = match utils::ping_url(&self.client, &url) {
+			Ok(response) => response,
+			Err(e) => return report.null(e),
+		};
+		let checked = utils::valid_schema(schema, &response)
+			.and_then(|_| self.check_identity(&response))
+			.and_then(|()| match &response["error"] {
+				Json::Null => Ok(()),
+				error => Err(VerifierError::QueryError(error.to_string())),
+			});
+		match checked {
+			Ok(()) => report.ok(None),
+			Err(e) => report.error(e),
+		}
+	}
+
+	/// An allele request without `referenceName` must be rejected with a 4xx status and an allele response with an
+	/// error
+	fn validate_bad_query(&self, url: Url, schema: &jsonschema::JSONSchema) -> EndpointReport {
+		log::info!("Validating \"Query\" with a bad request ({})", url);
+		let report = EndpointReport::new("Query", "Bad allele request", url.clone());
+		let (status, response) = match utils::request_url(&self.client, &url) {
+			Ok(response) => response,
+			Err(e) => return report.null(e),
+		};
+		let checked = response
+			.ok_or_else(|| VerifierError::BadErrorResponse(format!("HTTP {} with a body that is not JSON", status)))
+			.and_then(|response| {
+				if !(400..500).contains(&status) {
+					return Err(VerifierError::BadErrorResponse(format!(
+						"HTTP {} instead of a 4xx error",
+						status
+					)));
+				}
+				utils::valid_schema(schema, &response)?;
+				if response["error"].is_object() {
+					Ok(())
+				}
+				else {
+					Err(VerifierError::BadErrorResponse(String::from(
+						"no error in the allele response",
+					)))
+				}
+			});
+		match checked {
+			Ok(()) => report.ok(None),
+			Err(e) => report.error(e),
+		}
+	}
+
+	pub fn validate(self) -> BeaconOutput {
+		let mut output = Output::new();
+		let resolver = LocalResolver::default();
+		let info_schema = utils::compile_schema(&serde_json::from_str(INFO_SCHEMA).unwrap(), &resolver);
+		let allele_response_schema =
+			utils::compile_schema(&serde_json::from_str(ALLELE_RESPONSE_SCHEMA).unwrap(), &resolver);
+
+		// Validate info
+		log::info!("Validating \"Info\"");
+		let report = EndpointReport::new("Info", &self.name, self.url.clone());
+		output.push(match utils::valid_schema(&info_schema, &self.info) {
+			Ok(_) => report.ok(None),
+			Err(e) => report.error(e),
+		});
+
+		// Validate allele requests
+		if let Some(mut request) = self.allele_request() {
+			output.push(self.validate_query(self.query_url(&request), &allele_response_schema));
+			request.remove("referenceName");
+			output.push(self.validate_bad_query(self.query_url(&request), &allele_response_schema));
+		}
+		else {
+			log::warn!("No sampleAlleleRequests in /, the queries cannot be tested");
+			let url = self.query_url(&BTreeMap::new());
+			for name in ["Allele request", "Bad allele request"] {
+				output.push(EndpointReport::new("Query", name, url.clone()).null(VerifierError::NoSampleAlleleRequest));
+			}
+		}
+
+		let spec_version = self.info["apiVersion"]
+			.as_str()
+			.and_then(|api_version| semver::Version::parse(api_version.trim_start_matches('v')).ok());
+		BeaconOutput {
+			name: self.name,
+			url: self.url,
+			last_updated: chrono::offset::Utc::now().naive_utc().round_subsecs(6),
+			model: None,
+			framework: None,
+			spec_version: spec_version.map(|spec_version| spec_version.to_string()),
+			release: None,
+			handovers: BTreeMap::new(),
+			profile: None,
+			profiles: BTreeMap::new(),
+			requirements: Vec::new(),
+			entities: output.finish(),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+
+	use std::collections::BTreeMap;
+
+	use reqwest::blocking::Client;
+	use serde_json::json;
+	use url::Url;
+
+	use crate::checks::Severity;
+	use crate::output::BeaconOutput;
+	use crate::v1::BeaconV1;
+	use crate::waivers::Waivers;
+
+	fn beacon(info: serde_json::Value) -> Option<BeaconV1> {
+		BeaconV1::from_info(&Client::new(), &Url::parse("https://example.org/beacon").unwrap(), info)
+	}
+
+	#[test]
+	fn test_detect_v1() {
+		let beacon = beacon(json!({"id": "org.example.beacon", "name": "Beacon", "apiVersion": "v1.1.0"})).unwrap();
+		assert_eq!(beacon.name, "Beacon");
+	}
+
+	#[test]
+	fn test_detect_v2() {
+		let info = json!({
+			"meta": {"beaconId": "org.example.beacon", "apiVersion": "v2.0.0"},
+			"response": {"id": "org.example.beacon", "name": "Beacon", "apiVersion": "v2.0.0"}
+		});
+		assert!(beacon(info).is_none());
+		// v2 info without meta
+		assert!(beacon(json!({"id": "org.example.beacon", "apiVersion": "v2.0.0"})).is_none());
+		assert!(beacon(json!({"id": "org.example.beacon"})).is_none());
+	}
+
+	#[test]
+	fn test_query_url() {
+		let beacon = beacon(json!({
+			"id": "org.example.beacon",
+			"apiVersion": "1.0.0",
+			"sampleAlleleRequests": [{
+				"referenceName": "1",
+				"start": 10000,
+				"referenceBases": "A",
+				"assemblyId": "GRCh38",
+				"datasetIds": ["ds1", "ds2"]
+			}]
+		}))
+		.unwrap();
+		let request = beacon.allele_request().unwrap();
+		assert_eq!(
+			beacon.query_url(&request).as_str(),
+			"https://example.org/beacon/query?assemblyId=GRCh38&datasetIds=ds1&datasetIds=ds2&referenceBases=A&\
+			 referenceName=1&start=10000"
+		);
+		assert_eq!(
+			beacon.query_url(&BTreeMap::new()).as_str(),
+			"https://example.org/beacon/query"
+		);
+	}
+
+	#[test]
+	fn test_no_allele_request() {
+		let beacon = beacon(json!({"id": "org.example.beacon", "apiVersion": "1.0.0"})).unwrap();
+		assert!(beacon.allele_request().is_none());
+	}
+
+	#[test]
+	fn test_validate_waived() {
+		let beacon = beacon(json!({"id": "org.example.beacon", "apiVersion": "1.0.0"})).unwrap();
+		let mut output = beacon.validate();
+		let file = tempfile::NamedTempFile::new().unwrap();
+		std::fs::write(
+			file.path(),
+			r#"[{"beacon": "org.example.beacon", "entity": "Info", "justification": "Incomplete info",
+			"expires": "2099-01-01"}]"#,
+		)
+		.unwrap();
+		BeaconOutput::judge(
+			&mut output.entities,
+			&["https://example.org/beacon", "org.example.beacon"],
+			&BTreeMap::new(),
+			&Waivers::load(file.path()).unwrap(),
+		);
+		assert_eq!(output.entities["Info"][0].valid, Some(false));
+		assert_eq!(output.entities["Info"][0].waiver.as_deref(), Some("Incomplete info"));
+		assert!(output.entities["Query"]
+			.iter()
+			.all(|report| report.failure() == Some(Severity::Info)));
+	}
+}
@@ -0,0 +1,305 @@
+use std::fs::File;
+use std::path::Path;
+
+use chrono::NaiveDate;
+use serde::Deserialize;
+
+use crate::error::VerifierError;
+
+/// Known deviation of a beacon that is accepted until it expires
+#[derive(Debug, Clone, Deserialize)]
+pub struct Waiver {
+	/// Url, name or id of the beacon (any beacon if missing)
+	#[serde(default)]
+	pub beacon: Option<String>,
+	/// Entity of the reports (any entity if missing)
+	#[serde(default)]
+	pub entity: Option<String>,
+	/// JSON pointer of the errors, including the ones below it (any error if missing)
+	#[serde(default)]
+	pub pointer: Option<String>,
+	pub justification: String,
+	/// Last day the waiver applies
+	pub expires: NaiveDate,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Waivers(Vec<Waiver>);
+
+impl Waivers {
+	pub fn load(path: &Path) -> Result<Self, VerifierError> {
+		let file = File::open(path).map_err(|e| VerifierError::BadWaivers(path.to_path_buf(), e.to_string()))?;
+		let waivers: Vec<Waiver> =
+			serde_json::from_reader(file).map_err(|e| VerifierError::BadWaivers(path.to_path_buf(), e.to_string()))?;
+		let today = chrono::offset::Utc::now().naive_utc().date();
+		for waiver in waivers.iter().filter(|waiver| waiver.expires < today) {
+			log::warn!("Waiver \"{}\" expired on {}", waiver.justification, waiver.expires);
+		}
+		Ok(Self(waivers))
+	}
+
+	/// Justification of the waivers that cover every error of a report. `beacon` are the url, name and id of the
+	/// beacon.
+	pub fn find(&self, beacon: &[&str], entity: &str, error: &str, today: NaiveDate) -> Option<String> {
+		let mut justifications = Vec::new();
+		for line in error.lines().filter(|line| !line.trim().is_empty()) {
+			let waiver = self.0.iter().find(|waiver| {
+				waiver.expires >= today
+					&& waiver
+						.beacon
+						.as_ref()
+						.map_or(true, |waived| beacon.contains(&waived.as_str()))
+					&& waiver.entity.as_ref().map_or(true, |waived| waived == entity)
+					&& waiver.pointer.as_ref().map_or(true, |waived| {
+						pointer(line).map_or(false, |pointer| {
+							pointer.strip_prefix(waived.as_str()).map_or(false, |rest| {
+								rest.is_empty() || rest.starts_with('/') || waived.is_empty()
+							})
+						})
+					})
+			})?;
+			if !justifications.contains(&waiver.justification) {
+				justifications.push(waiver.justification.clone());
+			}
+		}
+		Some(justifications.join("; ")).filter(|justifications| !justifications.is_empty())
+	}
+}
+
+/// JSON pointer of an error of the schema validation (`<message> (<pointer>)`)
+fn pointer(line: &str) -> Option<&str> {
+	line.trim_end()
+		.strip_suffix(')')
+		.and_then(|line| line.rsplit_once('('))
+		.map(|(_, pointer)| pointer)
+		.filter(|pointer| pointer.is_empty() || pointer.starts_with('/'))
+}
+
+#[cfg(test)]
+mod tests {
+
+	use std::rc::Rc;
+
+	use chrono::NaiveDate;
+	use jsonschema::JSONSchema;
+	use serde_json::json;
+	use url::Url;
+
+	use crate::checks::{Check, Severity};
+	use crate::endpoint::BeaconEndpoint;
+	use crate::output::Output;
+	use crate::waivers::{Waiver, Waivers};
+
+	#[test]
+	fn test_find() {
+		let today = NaiveDate::from_ymd_opt(2024, 6, 1).unwrap();
+		let waiver = |pointer: &str, expires| Waiver {
+			beacon: Some(String::from("org.example.beacon")),
+			entity: Some(String::from("Individuals")),
+			pointer: Some(pointer.to_string()),
+			justification: format!("Known issue in {}", pointer),
+			expires: NaiveDate::from_ymd_opt(2024, expires, 1).unwrap(),
+		};
+		let waivers = Waivers(vec![waiver("/response/resultSets", 12), waiver("/meta", 1)]);
+		let beacon = ["https://example.org/api", "Beacon", "org.example.beacon"];
+		let error =
+			"Response does not match the schema: \"id\" is a required property (/response/resultSets/0/results/0)\n";
+
+		assert_eq!(
+			waivers.find(&beacon, "Individuals", error, today).as_deref(),
+			Some("Known issue in /response/resultSets")
+		);
+		assert!(waivers.find(&beacon, "Biosamples", error, today).is_none());
+		assert!(waivers
+			.find(&["https://example.org/other"], "Individuals", error, today)
+			.is_none());
+		// No JSON pointer
+		assert!(waivers
+			.find(
+				&beacon,
+				"Individuals",
+				"The answers of each granularity disagree (/response/resultSets)",
+				today
+			)
+			.is_some());
+		assert!(waivers
+			.find(
+				&beacon,
+				"Individuals",
+				"The answers of each granularity disagree (boolean: exists true)",
+				today
+			)
+			.is_none());
+	}
+
+	#[test]
+	fn test_find_expired() {
+		let waivers = Waivers(vec![Waiver {
+			beacon: None,
+			entity: None,
+			pointer: Some(String::from("/meta")),
+			justification: String::from("Known issue in /meta"),
+			expires: NaiveDate::from_ymd_opt(2024, 1, 1).unwrap(),
+		}]);
+		let error = "\"beaconId\" is a required property (/meta)";
+
+		assert_eq!(
+			waivers
+				.find(
+					&["Beacon"],
+					"Individuals",
+					error,
+					NaiveDate::from_ymd_opt(2024, 1, 1).unwrap()
+				)
+				.as_deref(),
+			Some("Known issue in /meta")
+		);
+		assert!(waivers
+			.find(
+				&["Beacon"],
+				"Individuals",
+				error,
+				NaiveDate::from_ymd_opt(2024, 1, 2).unwrap()
+			)
+			.is_none());
+	}
+
+	#[test]
+	fn test_find_partially_waived() {
+		let waivers = Waivers(vec![Waiver {
+			beacon: None,
+			entity: None,
+			pointer: Some(String::from("/response/resultSets")),
+			justification: String::from("Known issue in the results"),
+			expires: NaiveDate::from_ymd_opt(2024, 12, 1).unwrap(),
+		}]);
+		let errors = "\"id\" is a required property (/response/resultSets/0/results/0)\n\"beaconId\" is a required \
+		              property (/meta)\n";
+
+		// Every error must be waived
+		assert!(waivers
+			.find(
+				&["Beacon"],
+				"Individuals",
+				errors,
+				NaiveDate::from_ymd_opt(2024, 6, 1).unwrap()
+			)
+			.is_none());
+	}
+
+	#[test]
+	fn test_load() {
+		let file = tempfile::NamedTempFile::new().unwrap();
+		std::fs::write(file.path(), r#"[{"justification": "Known issue"}]"#).unwrap();
+		assert_eq!(
+			Waivers::load(file.path()).unwrap_err().to_string(),
+			format!(
+				"Bad waivers file {}: missing field `expires` at line 1 column 33",
+				file.path().display()
+			)
+		);
+
+		std::fs::write(
+			file.path(),
+			r#"[{"pointer": "/meta", "justification": "Known issue", "expires": "2024-01-01"}]"#,
+		)
+		.unwrap();
+		let waivers = Waivers::load(file.path()).unwrap();
+		assert_eq!(waivers.0.len(), 1);
+		assert_eq!(waivers.0[0].pointer.as_deref(), Some("/meta"));
+	}
+
+	#[test]
+	fn test_find_record_errors() {
+		let endpoint = BeaconEndpoint {
+			entity_name: String::from("Individuals"),
+			entry_type: String::from("individual"),
+			entity_schema: Rc::new(JSONSchema::compile(&json!({"required": ["id", "sex"]})).unwrap()),
+			name: String::from("Individuals all entries"),
+			template: String::from("/individuals"),
+			url: Url::parse("https://example.org/api/individuals").unwrap(),
+			integrity: Default::default(),
+			check: Check::Endpoint,
+		};
+		let response = json!({
+			"responseSummary": {"exists": true},
+			"response": {"resultSets": [{"results": [{"id": "ind1", "sex": {}}, {"id": "ind2"}]}]}
+		});
+		let error = endpoint
+			.validate_resultset_response(&response)
+			.error
+			.unwrap()
+			.to_string();
+		assert_eq!(
+			error,
+			"Response does not match the schema: \"sex\" is a required property (/response/resultSets/0/results/1)\n"
+		);
+
+		let waivers = Waivers(vec![Waiver {
+			beacon: None,
+			entity: Some(String::from("Individuals")),
+			pointer: Some(String::from("/response/resultSets")),
+			justification: String::from("Sex is not collected"),
+			expires: NaiveDate::from_ymd_opt(2024, 12, 1).unwrap(),
+		}]);
+		let today = NaiveDate::from_ymd_opt(2024, 6, 1).unwrap();
+		assert_eq!(
+			waivers.find(&["Beacon"], "Individuals", &error, today).as_deref(),
+			Some("Sex is not collected")
+		);
+	}
+
+	#[test]
+	fn test_judge_record_errors() {
+		let endpoint = BeaconEndpoint {
+			entity_name: String::from("Individuals"),
+			entry_type: String::from("individual"),
+			entity_schema: Rc::new(JSONSchema::compile(&json!({"required": ["id", "sex"]})).unwrap()),
+			name: String::from("Individuals all entries"),
+			template: String::from("/individuals"),
+			url: Url::parse("https://example.org/api/individuals").unwrap(),
+			integrity: Default::default(),
+			check: Check::Endpoint,
+		};
+		let response = json!({
+			"responseSummary": {"exists": true},
+			"response": {"resultSets": [{"results": [{"id": "ind1"}, {"sex": {}}]}]}
+		});
+		let mut output = Output::new();
+		output.push(endpoint.validate_resultset_response(&response));
+		let mut report = output.finish().remove("Individuals").unwrap().remove(0);
+		assert_eq!(report.errors.len(), 2);
+
+		let today = NaiveDate::from_ymd_opt(2024, 6, 1).unwrap();
+		let waiver = |pointer: &str| Waiver {
+			beacon: None,
+			entity: None,
+			pointer: Some(pointer.to_string()),
+			justification: format!("Known issue in {}", pointer),
+			expires: NaiveDate::from_ymd_opt(2024, 12, 1).unwrap(),
+		};
+
+		// The waived error of the first record does not hide the error of the second one
+		let waivers = Waivers(vec![waiver("/response/resultSets/0/results/0")]);
+		report.judge(
+			|severity| severity.or(Some(Severity::Error)),
+			|error| waivers.find(&["Beacon"], "Individuals", error, today),
+		);
+		assert_eq!(report.waiver, None);
+		assert_eq!(report.failure(), Some(Severity::Error));
+
+		let waivers = Waivers(vec![
+			waiver("/response/resultSets/0/results/0"),
+			waiver("/response/resultSets/0/results/1"),
+		]);
+		report.judge(
+			|severity| severity.or(Some(Severity::Error)),
+			|error| waivers.find(&["Beacon"], "Individuals", error, today),
+		);
+		assert_eq!(
+			report.waiver.as_deref(),
+			Some("Known issue in /response/resultSets/0/results/0; Known issue in /response/resultSets/0/results/1")
+		);
+		assert_eq!(report.failure(), None);
+	}
+}